@@ -8,8 +8,7 @@ impl AddSigned<u32> for u32 {
             let rhs = rhs as u32;
             self.checked_add(rhs)
         } else {
-            let mrhs = rhs.abs() as u32;
-            self.checked_sub(mrhs)
+            self.checked_sub(rhs.unsigned_abs())
         }
     }
 }
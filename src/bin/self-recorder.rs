@@ -0,0 +1,989 @@
+//! Разбор образа флеши самописца.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::{ArgEnum, Args, Parser, Subcommand};
+
+use self_recorder_packet::{
+    build_chains, fit_calibration, unpack_pages, write_measurements_csv, write_measurements_json,
+    BatteryForecast, Calibration, CalibrationPoint, Chains, CsvOptions, Error, FitDegrees,
+    FrefOverrides, HeaderLanguage, JsonLinesWriter, Measurement, PageData, PageKind, PageReader,
+    PrettyDuration, ReadError, SessionSeries, TelemetryPoint, TelemetryThresholds, TimestampStyle,
+    WallClock,
+};
+
+#[derive(Parser)]
+#[clap(
+    name = "self-recorder",
+    version,
+    about = "Разбор образа флеши самописца"
+)]
+struct Cli {
+    #[clap(flatten)]
+    source: Source,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct Source {
+    /// Размер страницы, байт
+    #[clap(
+        short = 's',
+        long,
+        global = true,
+        default_value_t = 4096,
+        value_parser = parse_page_size
+    )]
+    page_size: usize,
+
+    /// Опорная частота, Гц, для страниц без неё в заголовке
+    #[clap(short, long, global = true, default_value_t = 0.0)]
+    fref: f32,
+
+    /// Распаковывать страницы с несовпавшей CRC32
+    #[clap(long, global = true)]
+    ignore_inconsistant: bool,
+
+    /// Опорная частота сеанса вместо частоты из заголовков: "номер сеанса=Гц",
+    /// можно повторять. Номера сеансов - по цепочкам, как в stats; частоты
+    /// пересчитываются во всех форматах export, в том числе --per-page
+    #[clap(long, global = true, multiple_occurrences = true)]
+    fref_session: Vec<SessionFref>,
+}
+
+/// Размер страницы больше нуля
+fn parse_page_size(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("page size must be greater than zero".to_string()),
+        Ok(size) => Ok(size),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SessionFref {
+    session: usize,
+    fref: f32,
+}
+
+impl FromStr for SessionFref {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("expected SESSION=HZ, got \"{}\"", s);
+        let (session, fref) = s.split_once('=').ok_or_else(err)?;
+        let fref = fref.trim().parse::<f32>().map_err(|_| err())?;
+        if !fref.is_normal() || fref < 0.0 {
+            return Err(err());
+        }
+        Ok(SessionFref {
+            session: session.trim().parse().map_err(|_| err())?,
+            fref,
+        })
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Список страниц и их заголовки
+    Info {
+        /// Образ флеши
+        dump: PathBuf,
+    },
+    /// Проверить страницы, код возврата 1 - есть поврежденные страницы
+    Verify {
+        /// Образ флеши
+        dump: PathBuf,
+    },
+    /// Сохранить отсчеты
+    Export {
+        /// Образ флеши
+        dump: PathBuf,
+
+        /// Каталог для результатов, "-" - вывести в stdout
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+
+        /// Формат результатов
+        #[clap(long, arg_enum, default_value_t = Format::Csv)]
+        format: Format,
+
+        /// Файл на каждую страницу вместо файла на сеанс
+        #[clap(long)]
+        per_page: bool,
+
+        /// Файл коэффициентов: сохранить давление и температуру вместо частот
+        #[clap(long)]
+        calibration: Option<PathBuf>,
+
+        #[clap(flatten)]
+        csv: CsvArgs,
+    },
+    /// Сводка по образу: страницы, сеансы, разрывы и диапазоны частот
+    Stats {
+        /// Образ флеши
+        dump: PathBuf,
+    },
+    /// Температура процессора и заряд батареи по сеансам, оценка разряда батареи
+    Telemetry {
+        /// Образ флеши
+        dump: PathBuf,
+
+        /// Напряжение разряженной батареи, В
+        #[clap(long, default_value_t = 3.0)]
+        cutoff: f32,
+
+        /// Оценивать разряд по последним N минутам записи, по умолчанию - по всему сеансу
+        #[clap(long)]
+        window_min: Option<u64>,
+
+        /// Предупреждать о напряжении батареи ниже, В
+        #[clap(long, default_value_t = 3.2)]
+        v_bat_low: f32,
+
+        /// Предупреждать о температуре процессора ниже, *С
+        #[clap(long, default_value_t = -40.0, allow_hyphen_values = true)]
+        t_cpu_min: f32,
+
+        /// Предупреждать о температуре процессора выше, *С
+        #[clap(long, default_value_t = 85.0)]
+        t_cpu_max: f32,
+
+        /// Вывести ряды телеметрии сеансов в CSV вместо сводки
+        #[clap(long)]
+        series: bool,
+    },
+    /// Подобрать коэффициенты калибровки по образцовым измерениям
+    Fit {
+        /// Образ флеши
+        dump: PathBuf,
+
+        /// Образцовые измерения: строки "время от начала сеанса, мс; давление; температура"
+        #[clap(short, long)]
+        reference: PathBuf,
+
+        /// Номер сеанса в образе
+        #[clap(long, default_value_t = 0)]
+        session: usize,
+
+        /// Файл коэффициентов
+        #[clap(short, long)]
+        output: PathBuf,
+
+        /// Степень температуры по частоте температуры
+        #[clap(long, default_value_t = 3)]
+        temperature_degree: usize,
+
+        /// Степень давления по частоте давления
+        #[clap(long, default_value_t = 2)]
+        pressure_degree: usize,
+
+        /// Степень давления по частоте температуры
+        #[clap(long, default_value_t = 2)]
+        compensation_degree: usize,
+    },
+}
+
+#[derive(Args)]
+struct CsvArgs {
+    /// Разделитель полей CSV
+    #[clap(long, default_value_t = ';')]
+    delimiter: char,
+
+    /// Десятичная запятая вместо точки
+    #[clap(long)]
+    decimal_comma: bool,
+
+    /// Язык заголовков
+    #[clap(long, arg_enum, default_value_t = Lang::Ru)]
+    lang: Lang,
+
+    /// Знаков после десятичного разделителя
+    #[clap(long, default_value_t = 6)]
+    precision: usize,
+
+    /// Без блока сведений перед таблицей
+    #[clap(long)]
+    no_metadata: bool,
+
+    /// Запись времени отсчетов
+    #[clap(long, arg_enum, default_value_t = Time::Pretty)]
+    time: Time,
+
+    /// Единицы измерения в заголовках в скобках, без запятой
+    #[clap(long)]
+    unit_brackets: bool,
+
+    /// Время старта записи для --time iso и unix, мс от 1970-01-01 UTC,
+    /// по умолчанию - время RTC из заголовков страниц
+    #[clap(long)]
+    origin_unix_ms: Option<u64>,
+
+    /// Точка синхронизации для учета ухода часов самописца:
+    /// "время от старта записи, мс:время UTC, мс от 1970-01-01"
+    #[clap(long)]
+    sync: Option<SyncPoint>,
+}
+
+#[derive(Clone, Copy)]
+struct SyncPoint {
+    device_ms: u64,
+    unix_ms: u64,
+}
+
+impl FromStr for SyncPoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("expected DEVICE_MS:UNIX_MS, got \"{}\"", s);
+        let (device_ms, unix_ms) = s.split_once(':').ok_or_else(err)?;
+        Ok(SyncPoint {
+            device_ms: device_ms.trim().parse().map_err(|_| err())?,
+            unix_ms: unix_ms.trim().parse().map_err(|_| err())?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, ArgEnum)]
+enum Lang {
+    Ru,
+    En,
+}
+
+#[derive(Clone, Copy, ArgEnum)]
+enum Time {
+    Pretty,
+    Ms,
+    Iso,
+    Unix,
+}
+
+impl CsvArgs {
+    /// Часы для абсолютного времени: заданное время старта или время RTC
+    /// из заголовков, с учетом ухода по точке синхронизации
+    fn clock(&self, rtc: Option<WallClock>) -> Option<WallClock> {
+        let clock = self.origin_unix_ms.map(WallClock::new).or(rtc)?;
+        Some(match self.sync {
+            Some(sync) => clock.with_sync(sync.device_ms, sync.unix_ms),
+            None => clock,
+        })
+    }
+
+    fn options(&self, rtc: Option<WallClock>) -> io::Result<CsvOptions> {
+        let clock = || {
+            self.clock(rtc).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no RTC start time in the pages, use --origin-unix-ms",
+                )
+            })
+        };
+        Ok(CsvOptions {
+            delimiter: self.delimiter,
+            decimal_comma: self.decimal_comma,
+            language: match self.lang {
+                Lang::Ru => HeaderLanguage::Russian,
+                Lang::En => HeaderLanguage::English,
+            },
+            precision: self.precision,
+            metadata: !self.no_metadata,
+            timestamp: match self.time {
+                Time::Pretty => TimestampStyle::Pretty,
+                Time::Ms => TimestampStyle::Millis,
+                Time::Iso => TimestampStyle::Iso8601(clock()?),
+                Time::Unix => TimestampStyle::UnixMillis(clock()?),
+            },
+            unit_brackets: self.unit_brackets,
+        })
+    }
+}
+
+#[derive(Clone, Copy, ArgEnum)]
+enum Format {
+    Csv,
+    Json,
+    /// отсчет на строку, образ читается потоком, сеансы не сшиваются
+    Jsonl,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl Source {
+    fn pages(&self, dump: &Path) -> io::Result<PageReader<BufReader<File>>> {
+        Ok(PageReader::new(
+            BufReader::new(File::open(dump)?),
+            self.page_size,
+            self.fref,
+            self.ignore_inconsistant,
+        )
+        .set_parallel(rayon::current_num_threads()))
+    }
+
+    /// Все страницы образа, ошибка чтения файла прерывает разбор
+    fn read_all(&self, dump: &Path) -> io::Result<Vec<Result<PageData, Error>>> {
+        self.read_all_with(dump, |page| page)
+    }
+
+    /// Все страницы образа, каждая сразу после распаковки проходит через f
+    fn read_all_with<F: FnMut(PageData) -> PageData>(
+        &self,
+        dump: &Path,
+        mut f: F,
+    ) -> io::Result<Vec<Result<PageData, Error>>> {
+        let mut pages = self.pages(dump)?;
+        let res = pages
+            .by_ref()
+            .map(|page| match page {
+                Ok(page) => Ok(Ok(f(page))),
+                Err(ReadError::Page(e)) => Ok(Err(e)),
+                Err(ReadError::Io(e)) => Err(e),
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        warn_truncated(&pages);
+        Ok(res)
+    }
+
+    /// Цепочки страниц образа с заданными опорными частотами сеансов
+    fn chains(&self, dump: &Path) -> io::Result<Chains> {
+        Ok(self.build_chains(self.read_all(dump)?))
+    }
+
+    /// Цепочки образа без отсчетов: в памяти остаются только заголовки страниц,
+    /// сами страницы затем читаются по одной [`Source::read_page()`]
+    fn chain_layout(&self, dump: &Path) -> io::Result<Chains> {
+        let pages = self.read_all_with(dump, |mut page| {
+            for channel in page.channels.iter_mut() {
+                channel.records = Vec::new();
+            }
+            page
+        })?;
+        Ok(build_chains(pages))
+    }
+
+    /// Прочитать и распаковать страницу образа по её положению
+    fn read_page(&self, dump: &mut File, position: usize) -> io::Result<Result<PageData, Error>> {
+        dump.seek(SeekFrom::Start((position * self.page_size) as u64))?;
+        let mut page = Vec::with_capacity(self.page_size);
+        Read::by_ref(dump)
+            .take(self.page_size as u64)
+            .read_to_end(&mut page)?;
+        Ok(unpack_pages(&page, self.page_size, self.fref, self.ignore_inconsistant).remove(0))
+    }
+
+    /// Опорные частоты сеансов из --fref-session
+    fn fref_overrides(&self) -> FrefOverrides {
+        self.fref_session
+            .iter()
+            .fold(FrefOverrides::default(), |o, s| o.set(s.session, s.fref))
+    }
+
+    /// Опорные частоты, заданные сеансам, по положениям страниц этих сеансов.
+    /// Без --fref-session образ не читается
+    fn session_frefs(&self, dump: &Path) -> io::Result<HashMap<usize, f32>> {
+        let mut res = HashMap::new();
+        if self.fref_session.is_empty() {
+            return Ok(res);
+        }
+
+        let chains = self.chain_layout(dump)?;
+        for (session, fref) in self.fref_overrides().sessions {
+            if let Some(chain) = chains.sessions.get(session) {
+                res.extend(chain.positions.iter().map(|p| (*p, fref)));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Собрать цепочки и заменить опорные частоты сеансов
+    fn build_chains(&self, pages: Vec<Result<PageData, Error>>) -> Chains {
+        let mut chains = build_chains(pages);
+        chains.override_fref(&self.fref_overrides());
+        chains
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let res = match &cli.command {
+        Command::Info { dump } => info(&cli.source, dump),
+        Command::Verify { dump } => verify(&cli.source, dump),
+        Command::Export {
+            dump,
+            output,
+            format,
+            per_page,
+            calibration,
+            csv,
+        } => export(
+            &cli.source,
+            dump,
+            output,
+            *format,
+            *per_page,
+            calibration.as_deref(),
+            csv,
+        ),
+        Command::Stats { dump } => stats(&cli.source, dump),
+        Command::Telemetry {
+            dump,
+            cutoff,
+            window_min,
+            v_bat_low,
+            t_cpu_min,
+            t_cpu_max,
+            series,
+        } => telemetry(
+            &cli.source,
+            dump,
+            *cutoff,
+            *window_min,
+            &TelemetryThresholds {
+                v_bat_low: *v_bat_low,
+                t_cpu_min: *t_cpu_min,
+                t_cpu_max: *t_cpu_max,
+            },
+            *series,
+        ),
+        Command::Fit {
+            dump,
+            reference,
+            session,
+            output,
+            temperature_degree,
+            pressure_degree,
+            compensation_degree,
+        } => fit(
+            &cli.source,
+            dump,
+            reference,
+            *session,
+            output,
+            &FitDegrees {
+                temperature: *temperature_degree,
+                pressure: *pressure_degree,
+                pressure_compensation: *compensation_degree,
+            },
+        ),
+    };
+
+    match res {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Предупредить о неполной последней странице образа
+fn warn_truncated(pages: &PageReader<BufReader<File>>) {
+    if let Some(len) = pages.truncated_tail() {
+        eprintln!("Warning! last page truncated to {} bytes", len);
+    }
+}
+
+fn pretty_ms(ms: u64) -> PrettyDuration {
+    PrettyDuration(Duration::from_millis(ms))
+}
+
+fn info(source: &Source, dump: &Path) -> io::Result<ExitCode> {
+    let mut pages = source.pages(dump)?;
+    for (position, page) in pages.by_ref().enumerate() {
+        let page = match page {
+            Ok(page) => Ok(page),
+            Err(ReadError::Page(e)) => Err(e),
+            Err(ReadError::Io(e)) => return Err(e),
+        };
+        let kind = PageKind::of(&page);
+
+        match page {
+            Ok(page) => {
+                let h = &page.header;
+                let channels = page
+                    .channels
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{}/{}: {}",
+                            c.info.name,
+                            c.info.interleave_ratio,
+                            c.records.len()
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                println!(
+                    "{}: {:?} блок {} (пред. {}) начало {} интервал {} мс f_ref {} (расчет по {} {:?}) t_cpu {} v_bat {} данных {} байт каналы [{}]",
+                    position,
+                    kind,
+                    h.this_block_id,
+                    h.prev_block_id,
+                    pretty_ms(h.timestamp),
+                    h.base_interval_ms,
+                    h.f_ref,
+                    page.fref.value,
+                    page.fref.source,
+                    h.t_cpu,
+                    h.v_bat,
+                    h.data_len,
+                    channels.join(", ")
+                );
+            }
+            Err(e) => println!("{}: {:?} {}", position, kind, e),
+        }
+    }
+    warn_truncated(&pages);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn verify(source: &Source, dump: &Path) -> io::Result<ExitCode> {
+    let mut damaged = 0;
+    let mut total = 0;
+    let mut pages = source.pages(dump)?;
+    for (position, page) in pages.by_ref().enumerate() {
+        total += 1;
+        let page = match page {
+            Ok(page) => Ok(page),
+            Err(ReadError::Page(e)) => Err(e),
+            Err(ReadError::Io(e)) => return Err(e),
+        };
+
+        match PageKind::of(&page) {
+            PageKind::Valid | PageKind::Erased | PageKind::Blank => {}
+            kind => {
+                damaged += 1;
+                match page {
+                    Ok(page) => println!("{}: {:?} {:?}", position, kind, page.integrity),
+                    Err(e) => println!("{}: {:?} {}", position, kind, e),
+                }
+            }
+        }
+    }
+    warn_truncated(&pages);
+
+    println!("Страниц: {}, повреждено: {}", total, damaged);
+    Ok(if damaged == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Приемник результата: файл name в каталоге output или stdout, если output - "-"
+fn sink(output: &Path, name: String) -> io::Result<Box<dyn Write>> {
+    if output == Path::new("-") {
+        Ok(Box::new(io::stdout()))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(output.join(name))?)))
+    }
+}
+
+fn export(
+    source: &Source,
+    dump: &Path,
+    output: &Path,
+    format: Format,
+    per_page: bool,
+    calibration: Option<&Path>,
+    csv: &CsvArgs,
+) -> io::Result<ExitCode> {
+    let calibration = calibration.map(Calibration::load).transpose()?;
+    if calibration.is_some() && matches!(format, Format::Jsonl) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--calibration is not supported with --format jsonl",
+        ));
+    }
+
+    if csv.sync.is_some() && csv.origin_unix_ms.is_none() && matches!(format, Format::Jsonl) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--sync with --format jsonl requires --origin-unix-ms",
+        ));
+    }
+
+    if output != Path::new("-") {
+        fs::create_dir_all(output)?;
+    }
+
+    if let Format::Jsonl = format {
+        let mut w = JsonLinesWriter::new(sink(output, "samples.jsonl".to_string())?);
+        if let Some(clock) = csv.clock(None) {
+            w = w.with_clock(clock);
+        }
+        // сеансы нумеруются по цепочкам, как в остальных форматах, а страницы
+        // читаются по одной в порядке цепочек
+        let chains = source.chain_layout(dump)?;
+        let overrides = source.fref_overrides();
+        let mut file = File::open(dump)?;
+        let sessions = chains
+            .sessions
+            .iter()
+            .enumerate()
+            .map(|(n, c)| (Some(n), c));
+        for (session, chain) in sessions.chain(chains.orphans.iter().map(|c| (None, c))) {
+            w.start_chain(session);
+            let fref = session.and_then(|n| overrides.sessions.get(&n));
+            for position in chain.positions.iter() {
+                if let Ok(mut page) = source.read_page(&mut file, *position)? {
+                    if let Some(fref) = fref {
+                        page.override_fref(*fref);
+                    }
+                    w.write_page(&page)?;
+                }
+            }
+        }
+        w.into_inner().flush()?;
+    } else if per_page {
+        let frefs = source.session_frefs(dump)?;
+        let mut pages = source.pages(dump)?;
+        for (position, page) in pages.by_ref().enumerate() {
+            match page {
+                Ok(mut page) => {
+                    if let Some(fref) = frefs.get(&position) {
+                        page.override_fref(*fref);
+                    }
+                    let rtc = WallClock::from_header(&page.header);
+                    let mut w = sink(
+                        output,
+                        format!("page_{:06}.{}", position, format.extension()),
+                    )?;
+                    match (format, &calibration) {
+                        (Format::Csv, Some(c)) => {
+                            write_measurements_csv(&page.calibrate(c), &mut w, &csv.options(rtc)?)?
+                        }
+                        (_, Some(c)) => write_measurements_json(&page.calibrate(c), &mut w)?,
+                        (Format::Csv, None) => page.write_csv(&mut w, &csv.options(rtc)?)?,
+                        (_, None) => page.write_json_with(&mut w, csv.clock(rtc).as_ref())?,
+                    }
+                    w.flush()?;
+                }
+                Err(ReadError::Page(_)) => {}
+                Err(ReadError::Io(e)) => return Err(e),
+            }
+        }
+        // с --fref-session образ уже прочитан при разборе цепочек
+        if source.fref_session.is_empty() {
+            warn_truncated(&pages);
+        }
+    } else {
+        let chains = source.chains(dump)?;
+        for (n, chain) in chains
+            .sessions
+            .iter()
+            .chain(chains.orphans.iter())
+            .enumerate()
+        {
+            let series = chain.series();
+            let rtc = series.wall_clock();
+            let kind = if chain.is_session() {
+                "session"
+            } else {
+                "orphan"
+            };
+            for change in chain.fref_changes() {
+                eprintln!("Warning! {} {}: {}", kind, n, change);
+            }
+            let mut w = sink(
+                output,
+                format!(
+                    "{}_{:03}_{:06}.{}",
+                    kind,
+                    n,
+                    chain.positions[0],
+                    format.extension()
+                ),
+            )?;
+            match (format, &calibration) {
+                (Format::Csv, Some(c)) => {
+                    write_measurements_csv(&series.calibrate(c), &mut w, &csv.options(rtc)?)?
+                }
+                (_, Some(c)) => write_measurements_json(&series.calibrate(c), &mut w)?,
+                (Format::Csv, None) => series.write_csv(&mut w, &csv.options(rtc)?)?,
+                (_, None) => series.write_json_with(&mut w, csv.clock(rtc).as_ref())?,
+            }
+            w.flush()?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn stats(source: &Source, dump: &Path) -> io::Result<ExitCode> {
+    let pages = source.read_all(dump)?;
+
+    let mut kinds: Vec<(PageKind, usize)> = Vec::new();
+    for kind in pages.iter().map(PageKind::of) {
+        match kinds.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, count)) => *count += 1,
+            None => kinds.push((kind, 1)),
+        }
+    }
+    println!("Страниц: {}", pages.len());
+    for (kind, count) in kinds {
+        println!("  {:?}: {}", kind, count);
+    }
+
+    let chains = source.build_chains(pages);
+    print_chains_summary(&chains);
+
+    for (n, chain) in chains.sessions.iter().enumerate() {
+        let series = chain.series();
+        println!(
+            "Сеанс {}: страниц {}, начало {}, длительность {}",
+            n,
+            chain.pages.len(),
+            pretty_ms(series.start_ms),
+            pretty_ms(series_end(&series).saturating_sub(series.start_ms))
+        );
+        for event in chain.continuity() {
+            println!("  {}", event);
+        }
+        for change in chain.fref_changes() {
+            println!("  {}", change);
+        }
+        for channel in series.channels.iter() {
+            let freqs = channel.records.iter().map(|r| r.freq);
+            let (min, max) = freqs
+                .clone()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), f| {
+                    (min.min(f), max.max(f))
+                });
+            let mean = freqs.map(|f| f as f64).sum::<f64>() / channel.records.len().max(1) as f64;
+            println!(
+                "  {}: отсчетов {}, мин. {:.6} Гц, макс. {:.6} Гц, среднее {:.6} Гц",
+                channel.info.name,
+                channel.records.len(),
+                min,
+                max,
+                mean
+            );
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn telemetry(
+    source: &Source,
+    dump: &Path,
+    cutoff: f32,
+    window_min: Option<u64>,
+    thresholds: &TelemetryThresholds,
+    series: bool,
+) -> io::Result<ExitCode> {
+    let window_ms = window_min
+        .map(|m| {
+            m.checked_mul(60 * 1000).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "--window-min is too large")
+            })
+        })
+        .transpose()?;
+    let chains = source.chains(dump)?;
+
+    for (n, chain) in chains.sessions.iter().enumerate() {
+        let telemetry = chain.telemetry();
+        if series {
+            println!("# Сеанс {}", n);
+            telemetry.write_csv(io::stdout(), &CsvOptions::default())?;
+            continue;
+        }
+
+        let range = |f: fn(&TelemetryPoint) -> f32| {
+            telemetry
+                .points
+                .iter()
+                .map(f)
+                .filter(|v| v.is_finite())
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+                    (min.min(v), max.max(v))
+                })
+        };
+        let (t_min, t_max) = range(|p| p.t_cpu);
+        let (v_min, v_max) = range(|p| p.v_bat);
+        println!(
+            "Сеанс {}: страниц {}, t_cpu {} .. {} *С, v_bat {} .. {} В",
+            n,
+            telemetry.points.len(),
+            t_min,
+            t_max,
+            v_min,
+            v_max
+        );
+
+        match telemetry.battery_forecast(cutoff, window_ms) {
+            Some(BatteryForecast {
+                volts_per_hour,
+                v_bat_now,
+                remaining_ms: Some(remaining_ms),
+                ..
+            }) => println!(
+                "  Батарея: {:.3} В, {:.4} В/ч, до {} В осталось {}",
+                v_bat_now,
+                volts_per_hour,
+                cutoff,
+                pretty_ms(remaining_ms)
+            ),
+            Some(f) => println!(
+                "  Батарея: {:.3} В, {:.4} В/ч, не разряжается",
+                f.v_bat_now, f.volts_per_hour
+            ),
+            None => println!("  Батарея: мало точек для оценки разряда"),
+        }
+
+        for alert in telemetry.alerts(thresholds) {
+            println!(
+                "  {} блок {}: {:?} {}",
+                pretty_ms(alert.timestamp),
+                alert.block_id,
+                alert.kind,
+                alert.value
+            );
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Образцовые измерения: время, давление и температура через пробелы, `;` или `,`,
+/// `#` начинает комментарий
+fn read_reference(path: &Path) -> io::Result<Vec<Measurement>> {
+    let mut res = Vec::new();
+    for (n, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let fields = line
+            .split(|c: char| c.is_whitespace() || c == ';' || c == ',')
+            .filter(|f| !f.is_empty())
+            .collect::<Vec<_>>();
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}:{}: expected \"time; pressure; temperature\"",
+                    path.display(),
+                    n + 1
+                ),
+            )
+        };
+        match fields[..] {
+            [] => {}
+            [timestamp, pressure, temperature] => res.push(Measurement {
+                timestamp: timestamp.parse().map_err(|_| invalid())?,
+                pressure: pressure.parse().map_err(|_| invalid())?,
+                temperature: temperature.parse().map_err(|_| invalid())?,
+            }),
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(res)
+}
+
+fn fit(
+    source: &Source,
+    dump: &Path,
+    reference: &Path,
+    session: usize,
+    output: &Path,
+    degrees: &FitDegrees,
+) -> io::Result<ExitCode> {
+    let reference = read_reference(reference)?;
+    let chains = source.chains(dump)?;
+    let series = chains
+        .sessions
+        .get(session)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no session {} in {}", session, dump.display()),
+            )
+        })?
+        .series();
+
+    let channel = |name: &str| {
+        series
+            .channels
+            .iter()
+            .find(|c| c.info.name == name)
+            .map_or(&[][..], |c| c.records.as_slice())
+    };
+    let points = CalibrationPoint::from_records(&reference, channel("FP"), channel("FT"));
+    let fit = fit_calibration(&points, degrees)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fit.calibration.save(output)?;
+
+    println!("Точек: {} из {}", points.len(), reference.len());
+    println!(
+        "Давление: СКО {:e}, макс. {:e}",
+        fit.pressure.rms(),
+        fit.pressure.max_abs()
+    );
+    println!(
+        "Температура: СКО {:e}, макс. {:e}",
+        fit.temperature.rms(),
+        fit.temperature.max_abs()
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn series_end(series: &SessionSeries) -> u64 {
+    series
+        .channels
+        .iter()
+        .filter_map(|c| c.records.last())
+        .map(|r| r.timesstamp)
+        .max()
+        .unwrap_or(series.start_ms)
+}
+
+fn print_chains_summary(chains: &Chains) {
+    println!(
+        "Сеансов: {}, оборванных цепочек: {}, развилок: {}, слияний: {}, потерянных ссылок: {}",
+        chains.sessions.len(),
+        chains.orphans.len(),
+        chains.forks.len(),
+        chains.merges.len(),
+        chains.missing_links.len()
+    );
+    for fork in chains.forks.iter() {
+        println!(
+            "  развилка на странице {} (блок {}): продолжения {:?}, выбрано {}",
+            fork.position, fork.this_block_id, fork.candidates, fork.chosen
+        );
+    }
+    for merge in chains.merges.iter() {
+        match merge.chosen {
+            Some(chosen) => println!(
+                "  слияние на странице {} (блок {}): предшественники {:?}, выбран {}",
+                merge.position, merge.this_block_id, merge.predecessors, chosen
+            ),
+            None => println!(
+                "  слияние на странице {} (блок {}): предшественники {:?}, начало цепочки",
+                merge.position, merge.this_block_id, merge.predecessors
+            ),
+        }
+    }
+    for link in chains.missing_links.iter() {
+        println!(
+            "  страница {} (блок {}) ссылается на отсутствующий блок {}",
+            link.position, link.this_block_id, link.prev_block_id
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Пересчет частот датчиков в давление и температуру.
+//!
+//! Температура - полином от частоты температурного канала, давление -
+//! двумерный полином от частот обоих каналов, зависимость от частоты
+//! температуры компенсирует температурный уход датчика давления:
+//!
+//! ```text
+//! T = Σ T[j]·(Ft-Ft0)^j
+//! P = Σ Pi[j]·(Fp-Fp0)^i·(Ft-Ft0)^j
+//! ```
+//!
+//! Коэффициенты хранятся в текстовом файле, `#` начинает комментарий:
+//!
+//! ```text
+//! # датчик 1
+//! pressure_channel = FP
+//! temperature_channel = FT
+//! Fp0 = 1040
+//! Ft0 = 32640
+//! T = 80.7 -0.43 -3.2e-4
+//! P0 = 0.87 0.019
+//! P1 = 0.23 2.5e-4
+//! ```
+//!
+//! Каналы по умолчанию - FP и FT, отсутствующие строки Pi и недостающие
+//! коэффициенты считаются нулевыми. Степень по Fp-Fp0 не больше
+//! [`Calibration::MAX_DEGREE`].
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::csv_export::CsvWriter;
+use crate::{CsvOptions, PageData, Record, SessionSeries};
+
+/// Ошибка разбора файла коэффициентов
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum CalibrationError {
+    /// строка не вида `ключ = значения` или значение не разбирается, номер строки с 1
+    Syntax(usize),
+    /// неизвестный ключ, номер строки с 1
+    UnknownKey(usize),
+    /// нет обязательного ключа
+    Missing(&'static str),
+}
+
+impl fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalibrationError::Syntax(line) => write!(f, "line {}: syntax error", line),
+            CalibrationError::UnknownKey(line) => write!(f, "line {}: unknown key", line),
+            CalibrationError::Missing(key) => write!(f, "missing key {}", key),
+        }
+    }
+}
+
+impl std::error::Error for CalibrationError {}
+
+/// Коэффициенты пересчета частот в давление и температуру
+#[derive(PartialEq, Debug, Clone)]
+pub struct Calibration {
+    /// имя канала частоты давления
+    pub pressure_channel: String,
+    /// имя канала частоты температуры
+    pub temperature_channel: String,
+    /// смещение частоты давления, Гц
+    pub fp0: f64,
+    /// смещение частоты температуры, Гц
+    pub ft0: f64,
+    /// T[j] - коэффициент при (Ft-Ft0)^j
+    pub t: Vec<f64>,
+    /// p[i][j] - коэффициент при (Fp-Fp0)^i·(Ft-Ft0)^j
+    pub p: Vec<Vec<f64>>,
+}
+
+/// Давление и температура в момент отсчета канала давления
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Measurement {
+    /// время от начала записи, мс
+    pub timestamp: u64,
+    pub pressure: f32,
+    /// *С
+    pub temperature: f32,
+}
+
+/// Значение полинома по схеме Горнера
+fn polynom(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+impl Calibration {
+    /// Наибольшая степень по Fp-Fp0, строки P0..P16
+    pub const MAX_DEGREE: usize = 16;
+
+    /// Прочитать коэффициенты из файла
+    pub fn load<P: AsRef<Path>>(file: P) -> io::Result<Self> {
+        std::fs::read_to_string(file)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Сохранить коэффициенты в файл
+    pub fn save<P: AsRef<Path>>(&self, file: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(file)?);
+        write!(file, "{}", self)?;
+        file.flush()
+    }
+
+    /// Температура по частоте температурного канала
+    pub fn temperature(&self, ft: f32) -> f32 {
+        polynom(&self.t, ft as f64 - self.ft0) as f32
+    }
+
+    /// Давление по частотам каналов давления и температуры
+    pub fn pressure(&self, fp: f32, ft: f32) -> f32 {
+        let dt = ft as f64 - self.ft0;
+        let row = self
+            .p
+            .iter()
+            .map(|row| polynom(row, dt))
+            .collect::<Vec<_>>();
+        polynom(&row, fp as f64 - self.fp0) as f32
+    }
+
+    /// Пересчитать отсчеты каналов давления и температуры.
+    ///
+    /// Значение на каждый отсчет давления; частота температуры берется
+    /// из последнего отсчета температуры не позже него, а до первого
+    /// отсчета температуры - из первого. Без отсчетов температуры
+    /// результат пуст.
+    pub fn convert(&self, fp: &[Record], ft: &[Record]) -> Vec<Measurement> {
+        if ft.is_empty() {
+            return Vec::new();
+        }
+
+        let mut t = 0;
+        fp.iter()
+            .map(|p| {
+                while matches!(ft.get(t + 1), Some(r) if r.timesstamp <= p.timesstamp) {
+                    t += 1;
+                }
+                Measurement {
+                    timestamp: p.timesstamp,
+                    pressure: self.pressure(p.freq, ft[t].freq),
+                    temperature: self.temperature(ft[t].freq),
+                }
+            })
+            .collect()
+    }
+
+    /// Пересчитать отсчеты каналов с именами из калибровки,
+    /// без любого из каналов результат пуст
+    fn convert_channels<'a, I: Iterator<Item = (&'a str, &'a [Record])> + Clone>(
+        &self,
+        mut channels: I,
+    ) -> Vec<Measurement> {
+        let fp = channels
+            .clone()
+            .find(|(name, _)| *name == self.pressure_channel);
+        let ft = channels.find(|(name, _)| *name == self.temperature_channel);
+        match (fp, ft) {
+            (Some((_, fp)), Some((_, ft))) => self.convert(fp, ft),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Default for Calibration {
+    /// Нулевые коэффициенты для каналов FP и FT
+    fn default() -> Self {
+        Self {
+            pressure_channel: "FP".to_string(),
+            temperature_channel: "FT".to_string(),
+            fp0: 0.0,
+            ft0: 0.0,
+            t: Vec::new(),
+            p: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for Calibration {
+    type Err = CalibrationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut res = Calibration::default();
+        let mut fp0 = None;
+        let mut ft0 = None;
+        let mut t = None;
+
+        for (n, line) in s.lines().enumerate() {
+            let n = n + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+                None => return Err(CalibrationError::Syntax(n)),
+            };
+            let number = || {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| CalibrationError::Syntax(n))
+            };
+            let numbers = || {
+                value
+                    .split_whitespace()
+                    .map(|v| v.parse::<f64>().map_err(|_| CalibrationError::Syntax(n)))
+                    .collect::<Result<Vec<_>, _>>()
+            };
+
+            match key {
+                "pressure_channel" | "temperature_channel" if value.is_empty() => {
+                    return Err(CalibrationError::Syntax(n))
+                }
+                "pressure_channel" => res.pressure_channel = value.to_string(),
+                "temperature_channel" => res.temperature_channel = value.to_string(),
+                "Fp0" => fp0 = Some(number()?),
+                "Ft0" => ft0 = Some(number()?),
+                "T" => t = Some(numbers()?),
+                key if key.starts_with('P') => {
+                    let i = key[1..]
+                        .parse::<usize>()
+                        .map_err(|_| CalibrationError::UnknownKey(n))?;
+                    if i > Calibration::MAX_DEGREE {
+                        return Err(CalibrationError::Syntax(n));
+                    }
+                    if res.p.len() <= i {
+                        res.p.resize(i + 1, Vec::new());
+                    }
+                    res.p[i] = numbers()?;
+                }
+                _ => return Err(CalibrationError::UnknownKey(n)),
+            }
+        }
+
+        res.fp0 = fp0.ok_or(CalibrationError::Missing("Fp0"))?;
+        res.ft0 = ft0.ok_or(CalibrationError::Missing("Ft0"))?;
+        res.t = t.ok_or(CalibrationError::Missing("T"))?;
+        if res.p.is_empty() {
+            return Err(CalibrationError::Missing("P0"));
+        }
+        Ok(res)
+    }
+}
+
+impl fmt::Display for Calibration {
+    /// Коэффициенты в формате файла, без потери точности
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn row(f: &mut fmt::Formatter<'_>, key: &str, coeffs: &[f64]) -> fmt::Result {
+            write!(f, "{} =", key)?;
+            for c in coeffs {
+                write!(f, " {:e}", c)?;
+            }
+            writeln!(f)
+        }
+
+        writeln!(f, "pressure_channel = {}", self.pressure_channel)?;
+        writeln!(f, "temperature_channel = {}", self.temperature_channel)?;
+        writeln!(f, "Fp0 = {:e}", self.fp0)?;
+        writeln!(f, "Ft0 = {:e}", self.ft0)?;
+        row(f, "T", &self.t)?;
+        for (i, p) in self.p.iter().enumerate() {
+            row(f, &format!("P{}", i), p)?;
+        }
+        Ok(())
+    }
+}
+
+/// Записать давление и температуру в CSV
+pub fn write_measurements_csv<W: Write>(
+    measurements: &[Measurement],
+    w: W,
+    options: &CsvOptions,
+) -> io::Result<()> {
+    let mut w = CsvWriter::new(w, options);
+    w.columns(&[
+        options.tr("Время", "Time").to_string(),
+        options.tr("Давление", "Pressure").to_string(),
+        options.with_unit(
+            options.tr("Температура", "Temperature"),
+            options.tr("*С", "*C"),
+        ),
+    ])?;
+    for m in measurements {
+        w.values(m.timestamp, &[m.pressure, m.temperature])?;
+    }
+    Ok(())
+}
+
+impl PageData {
+    /// Давление и температура по отсчетам страницы
+    pub fn calibrate(&self, calibration: &Calibration) -> Vec<Measurement> {
+        calibration.convert_channels(
+            self.channels
+                .iter()
+                .map(|c| (c.info.name.as_str(), c.records.as_slice())),
+        )
+    }
+}
+
+impl SessionSeries {
+    /// Давление и температура по отсчетам всего сеанса
+    pub fn calibrate(&self, calibration: &Calibration) -> Vec<Measurement> {
+        calibration.convert_channels(
+            self.channels
+                .iter()
+                .map(|c| (c.info.name.as_str(), c.records.as_slice())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{polynom, Calibration, CalibrationError};
+    use crate::Record;
+
+    const FILE: &str = "# датчик\n\
+                        temperature_channel = T2 # не FT\n\
+                        Fp0 = 1000\n\
+                        Ft0 = 30000\n\
+                        \n\
+                        T = 20 0.5\n\
+                        P0 = 1 0.25\n\
+                        P2 = 0.125\n";
+
+    #[test]
+    fn polynoms() {
+        assert_eq!(polynom(&[], 3.0), 0.0);
+        assert_eq!(polynom(&[1.0, 2.0, 3.0], 2.0), 17.0);
+    }
+
+    #[test]
+    fn parse_and_print() {
+        let c = FILE.parse::<Calibration>().unwrap();
+        assert_eq!(c.pressure_channel, "FP");
+        assert_eq!(c.temperature_channel, "T2");
+        assert_eq!(c.t, vec![20.0, 0.5]);
+        assert_eq!(c.p, vec![vec![1.0, 0.25], vec![], vec![0.125]]);
+
+        assert_eq!(c.temperature(30004.0), 22.0);
+        // 1 + 0.25·4 + 0.125·2²
+        assert_eq!(c.pressure(1002.0, 30004.0), 2.5);
+
+        assert_eq!(c.to_string().parse::<Calibration>().unwrap(), c);
+    }
+
+    #[test]
+    fn errors() {
+        let parse = |s: &str| s.parse::<Calibration>().unwrap_err();
+        assert_eq!(parse("Fp0 = 1\nFt0\n"), CalibrationError::Syntax(2));
+        assert_eq!(parse("Fp0 = x\n"), CalibrationError::Syntax(1));
+        assert_eq!(parse("Fp0 = 1\nQ = 2\n"), CalibrationError::UnknownKey(2));
+        assert_eq!(parse("Px = 2\n"), CalibrationError::UnknownKey(1));
+        assert_eq!(
+            parse("Fp0 = 1\nP4294967295 = 1\n"),
+            CalibrationError::Syntax(2)
+        );
+        assert!("Fp0 = 1\nFt0 = 2\nT = 1\nP16 = 1\n"
+            .parse::<Calibration>()
+            .is_ok());
+        assert_eq!(
+            parse("Fp0 = 1\nFt0 = 2\nP0 = 1\n"),
+            CalibrationError::Missing("T")
+        );
+        assert_eq!(
+            parse("Fp0 = 1\nFt0 = 2\nT = 1\n"),
+            CalibrationError::Missing("P0")
+        );
+    }
+
+    #[test]
+    fn temperature_held_between_samples() {
+        let c = FILE.parse::<Calibration>().unwrap();
+        let r = |timesstamp, freq| Record {
+            timesstamp,
+            freq,
+            ..Default::default()
+        };
+        let fp = [r(0, 1000.0), r(100, 1000.0), r(200, 1000.0), r(300, 1000.0)];
+        let ft = [r(100, 30000.0), r(300, 30002.0)];
+
+        let t = c
+            .convert(&fp, &ft)
+            .iter()
+            .map(|m| (m.timestamp, m.temperature))
+            .collect::<Vec<_>>();
+        assert_eq!(t, vec![(0, 20.0), (100, 20.0), (200, 20.0), (300, 21.0)]);
+        assert!(c.convert(&fp, &[]).is_empty());
+    }
+}
@@ -0,0 +1,390 @@
+//! Подбор коэффициентов калибровки по образцовым измерениям.
+//!
+//! Коэффициенты модели из [`crate::Calibration`] подбираются методом
+//! наименьших квадратов. Система решается QR-разложением Хаусхолдера, без
+//! нормальных уравнений, степени частот для этого масштабируются в [-1, 1].
+//! Смещения частот Fp0 и Ft0 - средние частоты точек.
+
+use core::fmt;
+
+use crate::{Calibration, Measurement, Record};
+
+/// Точка калибровки: частоты каналов и образцовые давление и температура
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct CalibrationPoint {
+    /// частота канала давления, Гц
+    pub fp: f32,
+    /// частота канала температуры, Гц
+    pub ft: f32,
+    pub pressure: f64,
+    /// *С
+    pub temperature: f64,
+}
+
+impl CalibrationPoint {
+    /// Точки по образцовым измерениям и отсчетам каналов.
+    ///
+    /// Образцовому измерению соответствует отсчет давления с тем же
+    /// временем, частота температуры берется, как в
+    /// [`Calibration::convert()`]. Измерения без отсчета давления
+    /// пропускаются.
+    pub fn from_records(
+        reference: &[Measurement],
+        fp: &[Record],
+        ft: &[Record],
+    ) -> Vec<CalibrationPoint> {
+        if ft.is_empty() {
+            return Vec::new();
+        }
+
+        reference
+            .iter()
+            .filter_map(|m| {
+                let p = fp
+                    .binary_search_by_key(&m.timestamp, |r| r.timesstamp)
+                    .ok()?;
+                let t = ft.partition_point(|r| r.timesstamp <= m.timestamp).max(1) - 1;
+                Some(CalibrationPoint {
+                    fp: fp[p].freq,
+                    ft: ft[t].freq,
+                    pressure: m.pressure as f64,
+                    temperature: m.temperature as f64,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Степени полиномов модели
+#[derive(Debug, Clone, Copy)]
+pub struct FitDegrees {
+    /// степень температуры по Ft
+    pub temperature: usize,
+    /// степень давления по Fp
+    pub pressure: usize,
+    /// степень давления по Ft (температурная компенсация)
+    pub pressure_compensation: usize,
+}
+
+impl Default for FitDegrees {
+    fn default() -> Self {
+        Self {
+            temperature: 3,
+            pressure: 2,
+            pressure_compensation: 2,
+        }
+    }
+}
+
+/// Ошибки подбора коэффициентов
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum FitError {
+    /// точек меньше, чем коэффициентов
+    TooFewPoints {
+        /// сколько точек требуется
+        need: usize,
+        /// сколько точек есть
+        got: usize,
+    },
+    /// точки не определяют коэффициенты однозначно,
+    /// например все при одной температуре
+    Singular,
+    /// степень давления по Fp больше [`Calibration::MAX_DEGREE`]
+    DegreeTooHigh(usize),
+}
+
+impl fmt::Display for FitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FitError::TooFewPoints { need, got } => {
+                write!(f, "too few points: need {}, got {}", need, got)
+            }
+            FitError::Singular => write!(f, "points do not determine the coefficients"),
+            FitError::DegreeTooHigh(degree) => write!(
+                f,
+                "pressure degree {} exceeds {}",
+                degree,
+                Calibration::MAX_DEGREE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FitError {}
+
+/// Отклонения модели от образцовых значений
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Residuals {
+    /// модель минус образец, по точкам
+    pub values: Vec<f64>,
+}
+
+impl Residuals {
+    /// Среднеквадратичное отклонение
+    pub fn rms(&self) -> f64 {
+        if self.values.is_empty() {
+            return 0.0;
+        }
+        (self.values.iter().map(|v| v * v).sum::<f64>() / self.values.len() as f64).sqrt()
+    }
+
+    /// Наибольшее по модулю отклонение
+    pub fn max_abs(&self) -> f64 {
+        self.values.iter().fold(0.0, |max, v| max.max(v.abs()))
+    }
+}
+
+/// Результат подбора
+pub struct CalibrationFit {
+    /// коэффициенты для каналов FP и FT, имена каналов можно поменять
+    pub calibration: Calibration,
+    pub pressure: Residuals,
+    pub temperature: Residuals,
+}
+
+/// Подобрать коэффициенты по точкам калибровки
+pub fn fit_calibration(
+    points: &[CalibrationPoint],
+    degrees: &FitDegrees,
+) -> Result<CalibrationFit, FitError> {
+    if degrees.pressure > Calibration::MAX_DEGREE {
+        return Err(FitError::DegreeTooHigh(degrees.pressure));
+    }
+
+    let pressure_terms = (degrees.pressure + 1) * (degrees.pressure_compensation + 1);
+    let need = pressure_terms.max(degrees.temperature + 1);
+    if points.len() < need {
+        return Err(FitError::TooFewPoints {
+            need,
+            got: points.len(),
+        });
+    }
+
+    let mean = |f: fn(&CalibrationPoint) -> f32| {
+        points.iter().map(|p| f(p) as f64).sum::<f64>() / points.len() as f64
+    };
+    let fp0 = mean(|p| p.fp);
+    let ft0 = mean(|p| p.ft);
+
+    // отклонения частот, приведенные к [-1, 1]
+    let scale = |f: fn(&CalibrationPoint) -> f32, f0: f64| {
+        let max = points
+            .iter()
+            .fold(0.0, |max: f64, p| max.max((f(p) as f64 - f0).abs()));
+        if max > 0.0 {
+            max
+        } else {
+            1.0
+        }
+    };
+    let sp = scale(|p| p.fp, fp0);
+    let st = scale(|p| p.ft, ft0);
+    let x = points
+        .iter()
+        .map(|p| ((p.fp as f64 - fp0) / sp, (p.ft as f64 - ft0) / st))
+        .collect::<Vec<_>>();
+
+    let t_columns = (0..=degrees.temperature)
+        .map(|j| x.iter().map(|(_, t)| t.powi(j as i32)).collect())
+        .collect();
+    let t = least_squares(t_columns, points.iter().map(|p| p.temperature).collect())?
+        .iter()
+        .enumerate()
+        .map(|(j, c)| c / st.powi(j as i32))
+        .collect();
+
+    let p_columns = (0..=degrees.pressure)
+        .flat_map(|i| (0..=degrees.pressure_compensation).map(move |j| (i, j)))
+        .map(|(i, j)| {
+            x.iter()
+                .map(|(p, t)| p.powi(i as i32) * t.powi(j as i32))
+                .collect()
+        })
+        .collect();
+    let p = least_squares(p_columns, points.iter().map(|p| p.pressure).collect())?
+        .chunks(degrees.pressure_compensation + 1)
+        .enumerate()
+        .map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(j, c)| c / (sp.powi(i as i32) * st.powi(j as i32)))
+                .collect()
+        })
+        .collect();
+
+    let calibration = Calibration {
+        fp0,
+        ft0,
+        t,
+        p,
+        ..Default::default()
+    };
+    let pressure = Residuals {
+        values: points
+            .iter()
+            .map(|p| calibration.pressure(p.fp, p.ft) as f64 - p.pressure)
+            .collect(),
+    };
+    let temperature = Residuals {
+        values: points
+            .iter()
+            .map(|p| calibration.temperature(p.ft) as f64 - p.temperature)
+            .collect(),
+    };
+
+    Ok(CalibrationFit {
+        calibration,
+        pressure,
+        temperature,
+    })
+}
+
+/// Решение переопределенной системы A·x = b по наименьшим квадратам.
+/// columns - столбцы A одинаковой длины, не меньше их числа
+fn least_squares(mut columns: Vec<Vec<f64>>, mut b: Vec<f64>) -> Result<Vec<f64>, FitError> {
+    let n = columns.len();
+    let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let tolerance = columns.iter().map(|c| norm(c)).fold(0.0, f64::max) * 1e-12;
+
+    // A = Q·R, R остается в верхних треугольниках столбцов, b заменяется на Qт·b
+    for k in 0..n {
+        let alpha = norm(&columns[k][k..]);
+        if alpha <= tolerance {
+            return Err(FitError::Singular);
+        }
+        let alpha = if columns[k][k] > 0.0 { -alpha } else { alpha };
+
+        let mut v = columns[k][k..].to_vec();
+        v[0] -= alpha;
+        let vv = v.iter().map(|x| x * x).sum::<f64>();
+
+        let reflect = |y: &mut [f64]| {
+            let s = 2.0 * v.iter().zip(y.iter()).map(|(a, b)| a * b).sum::<f64>() / vv;
+            y.iter_mut().zip(v.iter()).for_each(|(y, v)| *y -= s * v);
+        };
+        for column in columns[k..].iter_mut() {
+            reflect(&mut column[k..]);
+        }
+        reflect(&mut b[k..]);
+    }
+
+    let mut x = vec![0.0; n];
+    for k in (0..n).rev() {
+        let s = (k + 1..n).map(|j| columns[j][k] * x[j]).sum::<f64>();
+        x[k] = (b[k] - s) / columns[k][k];
+    }
+    Ok(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit_calibration, least_squares, CalibrationPoint, FitDegrees, FitError};
+    use crate::{Calibration, Measurement, Record};
+
+    #[test]
+    fn solve() {
+        // y = 1 + 2·x, отклонения точек ортогональны модели
+        let x = [0.0, 1.0, 2.0, 3.0];
+        let columns = vec![vec![1.0; 4], x.to_vec()];
+        let b = vec![1.1, 2.9, 4.9, 7.1];
+        let c = least_squares(columns, b).unwrap();
+        assert!((c[0] - 1.0).abs() < 1e-9 && (c[1] - 2.0).abs() < 1e-9);
+
+        let columns = vec![vec![1.0; 3], vec![2.0; 3]];
+        assert_eq!(
+            least_squares(columns, vec![1.0; 3]),
+            Err(FitError::Singular)
+        );
+    }
+
+    fn points(f: impl Fn(f64, f64) -> (f64, f64)) -> Vec<CalibrationPoint> {
+        let mut res = Vec::new();
+        for i in 0..5 {
+            for j in 0..5 {
+                let fp = 1030.0 + 5.0 * i as f32;
+                let ft = 32500.0 + 60.0 * j as f32;
+                let (pressure, temperature) = f(fp as f64, ft as f64);
+                res.push(CalibrationPoint {
+                    fp,
+                    ft,
+                    pressure,
+                    temperature,
+                });
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn exact_model() {
+        let fit = fit_calibration(
+            &points(|fp, ft| {
+                let (x, t) = (fp - 1040.0, ft - 32600.0);
+                (
+                    0.9 + 0.02 * x + 2e-4 * t + 1e-3 * x * x + 1e-6 * x * t,
+                    80.0 - 0.43 * t - 3e-4 * t * t,
+                )
+            }),
+            &FitDegrees::default(),
+        )
+        .unwrap();
+
+        assert!(fit.pressure.max_abs() < 1e-6, "{}", fit.pressure.max_abs());
+        assert!(fit.temperature.max_abs() < 1e-4);
+        assert!(fit.pressure.rms() <= fit.pressure.max_abs());
+        assert_eq!(fit.calibration.fp0, 1040.0);
+        assert_eq!(fit.calibration.ft0, 32620.0);
+        assert_eq!(fit.calibration.p.len(), 3);
+        assert_eq!(fit.calibration.t.len(), 4);
+    }
+
+    #[test]
+    fn degenerate_points() {
+        let single_temperature = points(|fp, _| (fp, 20.0))
+            .into_iter()
+            .filter(|p| p.ft == 32500.0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fit_calibration(&single_temperature[..4], &FitDegrees::default()).err(),
+            Some(FitError::TooFewPoints { need: 9, got: 4 })
+        );
+
+        let mut repeated = single_temperature.clone();
+        repeated.extend_from_slice(&single_temperature);
+        assert_eq!(
+            fit_calibration(&repeated, &FitDegrees::default()).err(),
+            Some(FitError::Singular)
+        );
+
+        let degrees = FitDegrees {
+            pressure: Calibration::MAX_DEGREE + 1,
+            ..FitDegrees::default()
+        };
+        assert_eq!(
+            fit_calibration(&repeated, &degrees).err(),
+            Some(FitError::DegreeTooHigh(Calibration::MAX_DEGREE + 1))
+        );
+    }
+
+    #[test]
+    fn points_from_records() {
+        let r = |timesstamp, freq| Record {
+            timesstamp,
+            freq,
+            ..Default::default()
+        };
+        let m = |timestamp, pressure| Measurement {
+            timestamp,
+            pressure,
+            temperature: 20.0,
+        };
+        let fp = [r(0, 1.0), r(100, 2.0), r(200, 3.0)];
+        let ft = [r(100, 10.0), r(200, 20.0)];
+
+        let points =
+            CalibrationPoint::from_records(&[m(0, 0.5), m(150, 0.6), m(200, 0.7)], &fp, &ft);
+        let got = points.iter().map(|p| (p.fp, p.ft)).collect::<Vec<_>>();
+        assert_eq!(got, vec![(1.0, 10.0), (3.0, 20.0)]);
+        assert_eq!(points[1].pressure, 0.7f32 as f64);
+    }
+}
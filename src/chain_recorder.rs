@@ -0,0 +1,168 @@
+use alloc::vec::Vec;
+
+use crate::{
+    DataBlockPackerBuilder, DataPacketHeader, Error, InterleaveSchedule, PushResult, SampleEncoder,
+};
+
+/// Получатель готовых страниц, например запись во флеш
+pub trait PageSink {
+    fn write_page(&mut self, page: Vec<u8>);
+}
+
+impl<F: FnMut(Vec<u8>)> PageSink for F {
+    fn write_page(&mut self, page: Vec<u8>) {
+        self(page)
+    }
+}
+
+/// Запись цепочки страниц.
+///
+/// Владеет текущим упаковщиком и сам начинает следующую страницу, когда
+/// текущая заполнится: готовая страница уходит в [`PageSink`], новая получает
+/// `prev_block_id` = `this_block_id` предыдущей, `this_block_id` на 1 больше и
+/// `timestamp` тика, с которого она начинается.
+///
+/// Отсчеты тика пишутся в страницу одним куском, так что тик целиком попадает
+/// в одну страницу: если он уже не помещается, текущая страница завершается,
+/// а следующая начинается с этого тика. Ни один отсчет не теряется и не
+/// повторяется в двух страницах.
+///
+/// Расписание опроса каналов, как и в формате страницы, отсчитывается от
+/// начала каждой страницы: на первом тике страницы опрашиваются все каналы.
+pub struct ChainRecorder<S, CrcCalc>
+where
+    S: PageSink,
+    CrcCalc: FnMut(&[u8]) -> u32,
+{
+    builder: DataBlockPackerBuilder,
+    encoder: SampleEncoder,
+    schedule: InterleaveSchedule,
+    sink: S,
+    crc: CrcCalc,
+    /// номер базового тика от начала текущей страницы
+    tick: u64,
+    /// в текущую страницу записан хотя бы один отсчет
+    has_samples: bool,
+}
+
+impl<S, CrcCalc> ChainRecorder<S, CrcCalc>
+where
+    S: PageSink,
+    CrcCalc: FnMut(&[u8]) -> u32,
+{
+    /// builder - настройки страниц, номера блоков и время первой страницы цепочки
+    /// sink - получатель готовых страниц
+    /// crc - функция расчета CRC32
+    pub fn new(builder: DataBlockPackerBuilder, sink: S, crc: CrcCalc) -> Self {
+        let packer = builder.clone().build();
+        Self {
+            schedule: packer.schedule(),
+            encoder: SampleEncoder::new(packer),
+            builder,
+            sink,
+            crc,
+            tick: 0,
+            has_samples: false,
+        }
+    }
+
+    /// Записать один базовый тик.
+    /// results - последние результаты всех каналов, записываются только
+    /// опрашиваемые на этом тике.
+    /// Возвращает true, если при этом была завершена страница.
+    /// Если результат опрашиваемого канала не записать (в
+    /// [`crate::SampleEncoding::Delta`] разность с предыдущим результатом не
+    /// помещается в i32), тик не записывается: [`Error::ResultOutOfRange`]
+    pub fn push(&mut self, results: &[u32]) -> Result<bool, Error> {
+        assert_eq!(results.len(), self.encoder.packer().header.channels.len());
+
+        let due = (0..results.len())
+            .filter(|ch| self.schedule.is_due(self.tick, *ch))
+            .collect::<Vec<_>>();
+        if let Some(channel) = due
+            .iter()
+            .find(|ch| !self.encoder.fits(**ch, results[**ch]))
+        {
+            return Err(Error::ResultOutOfRange { channel: *channel });
+        }
+
+        if due.is_empty() {
+            self.tick += 1;
+            return Ok(false);
+        }
+
+        let samples = due.iter().map(|ch| (*ch, results[*ch])).collect::<Vec<_>>();
+        match self.encoder.push_all(&samples) {
+            PushResult::Success => {
+                self.has_samples = true;
+                self.tick += 1;
+                Ok(false)
+            }
+            PushResult::Full => {
+                // тик записан, следующая страница начнется со следующего
+                self.tick += 1;
+                self.next_page();
+                Ok(true)
+            }
+            _ => {
+                // тик не поместился, он начнет новую страницу
+                assert!(self.has_samples, "Page is too small for one tick");
+                self.next_page();
+                self.push(results)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Температура процессора для текущей и следующих страниц
+    pub fn set_tcpu(&mut self, t_cpu: f32) {
+        self.builder = self.builder.clone().set_tcpu(t_cpu);
+        self.encoder.packer_mut().header.t_cpu = t_cpu;
+    }
+
+    /// Заряд батареи для текущей и следующих страниц
+    pub fn set_vbat(&mut self, v_bat: f32) {
+        self.builder = self.builder.clone().set_vbat(v_bat);
+        self.encoder.packer_mut().header.v_bat = v_bat;
+    }
+
+    /// Заголовок текущей страницы
+    pub fn header(&self) -> &DataPacketHeader {
+        &self.encoder.packer().header
+    }
+
+    /// Завершить запись: отправить неполную текущую страницу, если в ней есть отсчеты
+    pub fn finish(mut self) -> S {
+        if self.has_samples {
+            let mut packer = self.encoder.into_packer();
+            packer.finish();
+            if let Some(page) = packer.to_result_full(&mut self.crc) {
+                self.sink.write_page(page);
+            }
+        }
+        self.sink
+    }
+
+    fn next_page(&mut self) {
+        let header = &self.encoder.packer().header;
+        let prev_block_id = header.this_block_id;
+        let timestamp = header.timestamp + self.tick * header.base_interval_ms as u64;
+
+        let packer = self
+            .builder
+            .clone()
+            .set_ids(prev_block_id, prev_block_id.wrapping_add(1))
+            .set_timestamp(timestamp)
+            .build();
+        let mut done =
+            core::mem::replace(&mut self.encoder, SampleEncoder::new(packer)).into_packer();
+
+        done.finish();
+        if let Some(page) = done.to_result_full(&mut self.crc) {
+            self.sink.write_page(page);
+        }
+
+        self.tick = 0;
+        self.has_samples = false;
+    }
+}
@@ -0,0 +1,234 @@
+//! Восстановление цепочек страниц по номерам блоков.
+//!
+//! Каждая страница ссылается на предыдущую через `prev_block_id`, сеанс записи
+//! начинается со стартовой страницы ([`DataPacketHeader::is_initial()`]).
+//! Номера блоков начинаются заново в каждом сеансе, поэтому одной ссылки мало:
+//! сначала связываются соседние во флеше страницы (с учетом перехода с конца
+//! флеши на начало), затем оборванные куски сшиваются по номерам блоков.
+//! Страница может продолжать предыдущую, только если её время не меньше.
+
+use std::collections::HashMap;
+
+use crate::{DataPacketHeader, Error, PageData};
+
+/// Цепочка страниц, упорядоченная по ссылкам
+pub struct Chain {
+    /// положение страниц в исходных данных
+    pub positions: Vec<usize>,
+    pub pages: Vec<PageData>,
+}
+
+impl Chain {
+    /// Цепочка начинается со стартовой страницы
+    pub fn is_session(&self) -> bool {
+        self.pages[0].header.is_initial()
+    }
+}
+
+/// Страница, которую могут продолжать несколько страниц
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Fork {
+    /// положение страницы
+    pub position: usize,
+    pub this_block_id: u32,
+    /// положения всех страниц-продолжений
+    pub candidates: Vec<usize>,
+    /// положение продолжения, попавшего в цепочку
+    pub chosen: usize,
+}
+
+/// Страница, которая может продолжать несколько страниц
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Merge {
+    /// положение страницы
+    pub position: usize,
+    pub this_block_id: u32,
+    /// положения всех страниц-предшественников
+    pub predecessors: Vec<usize>,
+    /// положение предшественника, за которым страница попала в цепочку,
+    /// цепочки остальных на них обрываются
+    pub chosen: Option<usize>,
+}
+
+/// Не найдена страница, на которую ссылается начало цепочки
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct MissingLink {
+    /// положение первой страницы цепочки
+    pub position: usize,
+    /// номер недостающего блока
+    pub prev_block_id: u32,
+    pub this_block_id: u32,
+}
+
+#[derive(Default)]
+pub struct Chains {
+    /// сеансы записи в порядке положения их стартовых страниц
+    pub sessions: Vec<Chain>,
+    /// цепочки, начало которых не найдено
+    pub orphans: Vec<Chain>,
+    pub forks: Vec<Fork>,
+    pub merges: Vec<Merge>,
+    /// начала цепочек из orphans, предшествующая страница которых не найдена
+    pub missing_links: Vec<MissingLink>,
+}
+
+/// Положения страниц по номеру блока, key - ключ страницы
+fn index_by<F: Fn(&DataPacketHeader) -> u32>(
+    headers: &[Option<DataPacketHeader>],
+    positions: impl Iterator<Item = usize>,
+    key: F,
+) -> HashMap<u32, Vec<usize>> {
+    let mut res = HashMap::<u32, Vec<usize>>::new();
+    for p in positions {
+        if let Some(header) = &headers[p] {
+            res.entry(key(header)).or_default().push(p);
+        }
+    }
+    res
+}
+
+fn precedes(prev: &DataPacketHeader, next: &DataPacketHeader) -> bool {
+    !next.is_initial()
+        && next.prev_block_id == prev.this_block_id
+        && next.timestamp >= prev.timestamp
+}
+
+/// Собрать цепочки из страниц в порядке их хранения, например из результата
+/// [`crate::unpack_pages()`]. Страницы с ошибками ни с чем не связываются.
+pub fn build_chains(pages: Vec<Result<PageData, Error>>) -> Chains {
+    let count = pages.len();
+    let mut pages = pages.into_iter().map(Result::ok).collect::<Vec<_>>();
+    let headers = pages
+        .iter()
+        .map(|p| p.as_ref().map(|p| p.header.clone()))
+        .collect::<Vec<_>>();
+    let linked = |prev: usize, next: usize| match (&headers[prev], &headers[next]) {
+        (Some(prev), Some(next)) => precedes(prev, next),
+        _ => false,
+    };
+
+    // соседние страницы
+    let mut next = vec![None; count];
+    let mut has_prev = vec![false; count];
+    if count > 1 {
+        for (p, next) in next.iter_mut().enumerate() {
+            let n = (p + 1) % count;
+            if linked(p, n) {
+                *next = Some(n);
+                has_prev[n] = true;
+            }
+        }
+    }
+
+    // куски без соседа-предшественника могут продолжать любую страницу
+    let heads = (0..count)
+        .filter(|p| headers[*p].is_some() && !has_prev[*p])
+        .collect::<Vec<_>>();
+    let heads_by_prev = index_by(&headers, heads.iter().cloned(), |h| h.prev_block_id);
+
+    let mut res = Chains::default();
+    for p in 0..count {
+        let mut candidates = next[p].into_iter().collect::<Vec<_>>();
+        let mut remote = headers[p]
+            .as_ref()
+            .and_then(|h| heads_by_prev.get(&h.this_block_id))
+            .map_or(&[][..], |heads| &heads[..])
+            .iter()
+            .cloned()
+            .filter(|h| *h != p && linked(p, *h))
+            .collect::<Vec<_>>();
+        // ближайшие по ходу записи первыми
+        remote.sort_by_key(|h| (h + count - p) % count);
+        candidates.extend(remote);
+
+        if candidates.len() > 1 {
+            res.forks.push(Fork {
+                position: p,
+                this_block_id: headers[p].as_ref().unwrap().this_block_id,
+                chosen: candidates[0],
+                candidates: candidates.clone(),
+            });
+        }
+        if next[p].is_none() {
+            next[p] = candidates.first().cloned();
+        }
+    }
+
+    let mut assigned = vec![false; count];
+    let mut came_from = vec![None; count];
+    let mut follow = |head: usize, pages: &mut Vec<Option<PageData>>| {
+        let mut chain = Chain {
+            positions: Vec::new(),
+            pages: Vec::new(),
+        };
+        let mut current = Some(head);
+        while let Some(p) = current {
+            if assigned[p] {
+                break;
+            }
+            assigned[p] = true;
+            came_from[p] = chain.positions.last().cloned();
+            chain.positions.push(p);
+            chain.pages.push(pages[p].take().unwrap());
+            current = next[p];
+        }
+        chain
+    };
+
+    for p in heads.iter().cloned() {
+        if headers[p].as_ref().unwrap().is_initial() {
+            res.sessions.push(follow(p, &mut pages));
+        }
+    }
+
+    // начало оставшихся цепочек: страницы, которые никто не продолжает
+    let mut continued = vec![false; count];
+    for p in 0..count {
+        if pages[p].is_some() {
+            if let Some(n) = next[p] {
+                continued[n] = true;
+            }
+        }
+    }
+    let orphan_heads = (0..count)
+        .filter(|p| pages[*p].is_some() && !continued[*p])
+        .chain(0..count)
+        .collect::<Vec<_>>();
+    let by_id = index_by(&headers, 0..count, |h| h.this_block_id);
+    for p in orphan_heads {
+        if pages[p].is_some() {
+            // предшественник есть, но продолжен другой страницей - это развилка
+            let header = headers[p].as_ref().unwrap();
+            let has_predecessor = by_id
+                .get(&header.prev_block_id)
+                .map_or(false, |qs| qs.iter().any(|q| linked(*q, p)));
+            if !has_predecessor {
+                res.missing_links.push(MissingLink {
+                    position: p,
+                    prev_block_id: header.prev_block_id,
+                    this_block_id: header.this_block_id,
+                });
+            }
+            res.orphans.push(follow(p, &mut pages));
+        }
+    }
+
+    let mut predecessors = vec![Vec::new(); count];
+    for (p, n) in next.iter().enumerate() {
+        if let Some(n) = n {
+            predecessors[*n].push(p);
+        }
+    }
+    for (n, predecessors) in predecessors.into_iter().enumerate() {
+        if predecessors.len() > 1 {
+            res.merges.push(Merge {
+                position: n,
+                this_block_id: headers[n].as_ref().unwrap().this_block_id,
+                predecessors,
+                chosen: came_from[n],
+            });
+        }
+    }
+
+    res
+}
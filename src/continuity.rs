@@ -0,0 +1,267 @@
+//! Проверка непрерывности записи между соседними страницами цепочки.
+//!
+//! Конец страницы вычисляется по количеству отсчетов каждого канала, его
+//! делителю и базовому интервалу, а не по времени отсчетов, так что потерянные
+//! или лишние отсчеты тоже видны как несовпадение времени.
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use crate::{Chain, InterleaveSchedule, PageData, PrettyDuration};
+
+/// Нарушение непрерывности между страницей page и предыдущей
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ContinuityEvent {
+    /// следующая страница начинается позже, чем закончилась предыдущая
+    Gap {
+        /// номер страницы в цепочке
+        page: usize,
+        /// ожидаемое время начала страницы, мс
+        expected_ms: u64,
+        /// записанное время начала страницы, мс
+        actual_ms: u64,
+    },
+    /// следующая страница начинается раньше, чем закончилась предыдущая,
+    /// например после перезагрузки прибора
+    Overlap {
+        page: usize,
+        expected_ms: u64,
+        actual_ms: u64,
+    },
+    /// номер блока не на 1 больше номера предыдущего
+    IdJump {
+        page: usize,
+        expected_id: u32,
+        actual_id: u32,
+    },
+}
+
+impl Display for ContinuityEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            ContinuityEvent::Gap {
+                page,
+                expected_ms,
+                actual_ms,
+            } => write!(
+                f,
+                "Страница {}: пропуск {} (ожидалось начало в {}, записано {})",
+                page,
+                PrettyDuration(Duration::from_millis(actual_ms - expected_ms)),
+                PrettyDuration(Duration::from_millis(expected_ms)),
+                PrettyDuration(Duration::from_millis(actual_ms)),
+            ),
+            ContinuityEvent::Overlap {
+                page,
+                expected_ms,
+                actual_ms,
+            } => write!(
+                f,
+                "Страница {}: перекрытие {} (ожидалось начало в {}, записано {})",
+                page,
+                PrettyDuration(Duration::from_millis(expected_ms - actual_ms)),
+                PrettyDuration(Duration::from_millis(expected_ms)),
+                PrettyDuration(Duration::from_millis(actual_ms)),
+            ),
+            ContinuityEvent::IdJump {
+                page,
+                expected_id,
+                actual_id,
+            } => write!(
+                f,
+                "Страница {}: номер блока {}, ожидался {}",
+                page, actual_id, expected_id
+            ),
+        }
+    }
+}
+
+impl PageData {
+    /// Номер последнего записанного тика страницы и записаны ли на нем
+    /// все опрашиваемые каналы, None - в странице нет отсчетов
+    fn last_tick(&self) -> Option<(u64, bool)> {
+        let schedule = InterleaveSchedule::for_channels(&self.header.channels);
+        let last_sample_tick = |c: &crate::ChannelData| {
+            (c.records.len() as u64)
+                .checked_sub(1)
+                .map(|n| n * c.info.interleave_ratio as u64)
+        };
+
+        let last = self.channels.iter().filter_map(last_sample_tick).max()?;
+        let complete = self
+            .channels
+            .iter()
+            .enumerate()
+            .all(|(ch, c)| !schedule.is_due(last, ch) || last_sample_tick(c) == Some(last));
+
+        Some((last, complete))
+    }
+
+    /// Время, с которого должна начинаться следующая страница цепочки, мс.
+    /// Если последний тик записан не полностью, следующая страница повторяет его.
+    /// None - в странице нет отсчетов
+    pub fn expected_next_timestamp(&self) -> Option<u64> {
+        let (last, complete) = self.last_tick()?;
+        let next_tick = if complete { last + 1 } else { last };
+
+        Some(self.header.timestamp + next_tick * self.header.base_interval_ms as u64)
+    }
+}
+
+/// Проверить непрерывность цепочки страниц.
+/// Время не проверяется после страниц без отсчетов
+pub fn analyze_continuity(pages: &[PageData]) -> Vec<ContinuityEvent> {
+    let mut res = Vec::new();
+
+    for (page, pair) in pages.windows(2).enumerate().map(|(i, w)| (i + 1, w)) {
+        let (prev, this) = (&pair[0].header, &pair[1].header);
+
+        let expected_id = prev.this_block_id.wrapping_add(1);
+        if this.this_block_id != expected_id || this.prev_block_id != prev.this_block_id {
+            res.push(ContinuityEvent::IdJump {
+                page,
+                expected_id,
+                actual_id: this.this_block_id,
+            });
+        }
+
+        if let Some(expected_ms) = pair[0].expected_next_timestamp() {
+            let actual_ms = this.timestamp;
+            if actual_ms > expected_ms {
+                res.push(ContinuityEvent::Gap {
+                    page,
+                    expected_ms,
+                    actual_ms,
+                });
+            } else if actual_ms < expected_ms {
+                res.push(ContinuityEvent::Overlap {
+                    page,
+                    expected_ms,
+                    actual_ms,
+                });
+            }
+        }
+    }
+
+    res
+}
+
+impl Chain {
+    /// Проверить непрерывность цепочки
+    pub fn continuity(&self) -> Vec<ContinuityEvent> {
+        analyze_continuity(&self.pages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze_continuity, ContinuityEvent};
+    use crate::{
+        ChannelData, ChannelInfo, ChecksumStatus, DataPacketHeader, Fref, FrefSource, PageData,
+        PageIntegrity, Record, SampleEncoding,
+    };
+
+    /// Страница с заданным количеством отсчетов каналов с делителями 1 и 2
+    fn page(prev: u32, this: u32, timestamp: u64, counts: [usize; 2]) -> PageData {
+        let channels = vec![ChannelInfo::new("FP", 1, 1), ChannelInfo::new("FT", 1, 2)];
+        PageData {
+            header: DataPacketHeader {
+                prev_block_id: prev,
+                this_block_id: this,
+                timestamp,
+                rtc_start_ms: None,
+                f_ref: 1.0,
+                base_interval_ms: 100,
+                channels: channels.clone(),
+                t_cpu: 0.0,
+                v_bat: 0.0,
+                data_len: 0,
+                data_crc32: 0,
+                sample_encoding: SampleEncoding::Delta,
+            },
+            fref: Fref {
+                value: 1.0,
+                source: FrefSource::Header,
+            },
+            consistant: true,
+            integrity: PageIntegrity {
+                header: ChecksumStatus::Valid,
+                data: ChecksumStatus::Valid,
+            },
+            channels: channels
+                .into_iter()
+                .zip(counts.iter())
+                .map(|(info, count)| ChannelData {
+                    info,
+                    records: vec![Record::default(); *count],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn expected_end() {
+        // тики 0..9, на последнем опрашивается только FP
+        assert_eq!(
+            page(0, 0, 1000, [10, 5]).expected_next_timestamp(),
+            Some(2000)
+        );
+        // на тике 10 записан только FP, следующая страница повторит тик 10
+        assert_eq!(
+            page(0, 0, 1000, [11, 5]).expected_next_timestamp(),
+            Some(2000)
+        );
+        // тик 10 записан полностью
+        assert_eq!(
+            page(0, 0, 1000, [11, 6]).expected_next_timestamp(),
+            Some(2100)
+        );
+        assert_eq!(page(0, 0, 1000, [0, 0]).expected_next_timestamp(), None);
+    }
+
+    #[test]
+    fn continuous() {
+        let pages = [
+            page(0, 0, 0, [10, 5]),
+            page(0, 1, 1000, [11, 5]),
+            page(1, 2, 2000, [4, 2]),
+        ];
+        assert!(analyze_continuity(&pages).is_empty());
+    }
+
+    #[test]
+    fn gap_overlap_and_id_jump() {
+        let pages = [
+            page(0, 0, 0, [10, 5]),
+            page(0, 1, 1500, [10, 5]),
+            page(1, 3, 2000, [10, 5]),
+        ];
+
+        assert_eq!(
+            analyze_continuity(&pages),
+            vec![
+                ContinuityEvent::Gap {
+                    page: 1,
+                    expected_ms: 1000,
+                    actual_ms: 1500,
+                },
+                ContinuityEvent::IdJump {
+                    page: 2,
+                    expected_id: 2,
+                    actual_id: 3,
+                },
+                ContinuityEvent::Overlap {
+                    page: 2,
+                    expected_ms: 2500,
+                    actual_ms: 2000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_page_skips_time_check() {
+        let pages = [page(0, 0, 0, [0, 0]), page(0, 1, 5000, [10, 5])];
+        assert!(analyze_continuity(&pages).is_empty());
+    }
+}
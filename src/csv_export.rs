@@ -0,0 +1,312 @@
+//! Настройки и запись CSV.
+//!
+//! Настройки по умолчанию дают исторический формат: разделитель `;`,
+//! десятичная точка, русские заголовки, 6 знаков после точки, блок сведений
+//! о странице перед таблицей и время от начала записи в виде `ЧЧ:ММ:СС.мс`.
+
+use std::fmt::Display;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::{ChannelInfo, PrettyDuration, Record, WallClock};
+
+/// Язык заголовков и сведений
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum HeaderLanguage {
+    Russian,
+    English,
+}
+
+/// Запись времени отсчетов
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TimestampStyle {
+    /// время от начала записи, `[дни d ]ЧЧ:ММ:СС.мс`
+    Pretty,
+    /// время от начала записи, мс
+    Millis,
+    /// абсолютное время UTC в ISO-8601, `2021-03-04T05:06:07.089Z`
+    Iso8601(WallClock),
+    /// абсолютное время UTC, мс от 1970-01-01
+    UnixMillis(WallClock),
+}
+
+/// Настройки CSV
+#[derive(Debug, Clone, Copy)]
+pub struct CsvOptions {
+    /// разделитель полей
+    pub delimiter: char,
+    /// десятичная запятая вместо точки
+    pub decimal_comma: bool,
+    pub language: HeaderLanguage,
+    /// знаков после десятичного разделителя
+    pub precision: usize,
+    /// блок сведений о странице или сеансе перед таблицей
+    pub metadata: bool,
+    pub timestamp: TimestampStyle,
+    /// единицы измерения в заголовках в скобках: `Pressure frequency [Hz]`,
+    /// иначе через запятую: `Pressure frequency, Hz`
+    pub unit_brackets: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ';',
+            decimal_comma: false,
+            language: HeaderLanguage::Russian,
+            precision: 6,
+            metadata: true,
+            timestamp: TimestampStyle::Pretty,
+            unit_brackets: false,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Настройки для pandas и других англоязычных программ:
+    /// разделитель `,`, английские заголовки без запятых, время в мс,
+    /// без блока сведений
+    pub fn plain() -> Self {
+        Self {
+            delimiter: ',',
+            language: HeaderLanguage::English,
+            metadata: false,
+            timestamp: TimestampStyle::Millis,
+            unit_brackets: true,
+            ..Default::default()
+        }
+    }
+
+    /// Выбрать текст на языке заголовков
+    pub(crate) fn tr<'s>(&self, ru: &'s str, en: &'s str) -> &'s str {
+        match self.language {
+            HeaderLanguage::Russian => ru,
+            HeaderLanguage::English => en,
+        }
+    }
+
+    /// Заголовок столбца канала: у каналов страниц v1 исторические названия
+    pub(crate) fn channel_title<'s>(&self, name: &'s str) -> &'s str {
+        match name {
+            "FP" => self.tr("Частота давления", "Pressure frequency"),
+            "FT" => self.tr("Частота температуры", "Temperature frequency"),
+            name => name,
+        }
+    }
+
+    /// Заголовок столбца с единицами измерения
+    pub(crate) fn with_unit(&self, title: &str, unit: &str) -> String {
+        if self.unit_brackets {
+            format!("{} [{}]", title, unit)
+        } else {
+            format!("{}, {}", title, unit)
+        }
+    }
+
+    /// Время от начала записи ms в выбранном виде
+    pub(crate) fn format_timestamp(&self, ms: u64) -> String {
+        match self.timestamp {
+            TimestampStyle::Pretty => PrettyDuration(Duration::from_millis(ms)).to_string(),
+            TimestampStyle::Millis => ms.to_string(),
+            TimestampStyle::Iso8601(clock) => clock.iso8601(ms),
+            TimestampStyle::UnixMillis(clock) => clock.unix_ms(ms).to_string(),
+        }
+    }
+
+    pub(crate) fn format_value(&self, v: f32) -> String {
+        let s = format!("{:.*}", self.precision, v);
+        if self.decimal_comma {
+            s.replace('.', ",")
+        } else {
+            s
+        }
+    }
+}
+
+/// Время UTC в ISO-8601 с миллисекундами
+pub(crate) fn iso8601(unix_ms: u64) -> String {
+    const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+    let days = (unix_ms / MS_PER_DAY) as i64;
+    let ms = unix_ms % MS_PER_DAY;
+
+    // дата по номеру дня от 1970-01-01 в пролептическом григорианском календаре
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Запись таблицы CSV с заданными настройками
+pub(crate) struct CsvWriter<'o, W: Write> {
+    w: W,
+    options: &'o CsvOptions,
+}
+
+impl<'o, W: Write> CsvWriter<'o, W> {
+    pub(crate) fn new(w: W, options: &'o CsvOptions) -> Self {
+        Self { w, options }
+    }
+
+    /// Строка из полей, поля с разделителем, кавычкой или переводом строки
+    /// заключаются в кавычки
+    fn line<I: IntoIterator<Item = D>, D: Display>(&mut self, fields: I) -> std::io::Result<()> {
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                write!(self.w, "{}", self.options.delimiter)?;
+            }
+            let field = field.to_string();
+            if field.contains(&[self.options.delimiter, '"', '\n'][..]) {
+                write!(self.w, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                self.w.write_all(field.as_bytes())?;
+            }
+        }
+        self.w.write_all(b"\n")
+    }
+
+    /// Строка блока сведений, пропускается, если блок отключен
+    pub(crate) fn meta<I: IntoIterator<Item = D>, D: Display>(
+        &mut self,
+        fields: I,
+    ) -> std::io::Result<()> {
+        if self.options.metadata {
+            self.line(fields)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Строка заголовков таблицы
+    pub(crate) fn header(&mut self, channels: &[ChannelInfo]) -> std::io::Result<()> {
+        let options = self.options;
+        let unit = options.tr("Гц", "Hz");
+        let titles = channels
+            .iter()
+            .map(|c| options.with_unit(options.channel_title(&c.name), unit));
+        self.line(core::iter::once(options.tr("Время", "Time").to_string()).chain(titles))
+    }
+
+    /// Строка таблицы: время от начала записи и значения каналов
+    pub(crate) fn row(&mut self, ms: u64, values: &[Record]) -> std::io::Result<()> {
+        let options = self.options;
+        self.line(
+            core::iter::once(options.format_timestamp(ms))
+                .chain(values.iter().map(|v| options.format_value(v.freq))),
+        )
+    }
+
+    /// Строка заголовков произвольных столбцов
+    pub(crate) fn columns(&mut self, titles: &[String]) -> std::io::Result<()> {
+        self.line(titles)
+    }
+
+    /// Строка таблицы: время от начала записи и значения
+    pub(crate) fn values(&mut self, ms: u64, values: &[f32]) -> std::io::Result<()> {
+        let options = self.options;
+        self.line(
+            core::iter::once(options.format_timestamp(ms))
+                .chain(values.iter().map(|v| options.format_value(*v))),
+        )
+    }
+
+    /// Строка разрыва в отсчетах
+    pub(crate) fn gap(&mut self, from_ms: u64, to_ms: u64) -> std::io::Result<()> {
+        let options = self.options;
+        self.line([
+            options.tr("Разрыв", "Gap").to_string(),
+            options.format_timestamp(from_ms),
+            options.format_timestamp(to_ms),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{iso8601, CsvOptions, CsvWriter, HeaderLanguage, TimestampStyle};
+    use crate::{ChannelInfo, Record, WallClock};
+
+    #[test]
+    fn iso8601_dates() {
+        assert_eq!(iso8601(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(iso8601(1_614_834_367_089), "2021-03-04T05:06:07.089Z");
+        assert_eq!(iso8601(951_868_799_999), "2000-02-29T23:59:59.999Z");
+    }
+
+    fn table(options: &CsvOptions) -> String {
+        let mut buf = Vec::new();
+        let mut w = CsvWriter::new(&mut buf, options);
+        w.meta(["Базовый интервал", "100"]).unwrap();
+        w.header(&[ChannelInfo::new("FP", 1, 1), ChannelInfo::new("T2", 1, 1)])
+            .unwrap();
+        let values = [
+            Record {
+                timesstamp: 1500,
+                freq: 32768.125,
+                ..Default::default()
+            },
+            Record {
+                timesstamp: 1500,
+                freq: 0.5,
+                ..Default::default()
+            },
+        ];
+        w.row(1500, &values).unwrap();
+        w.gap(1600, 61_000).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn legacy_layout() {
+        assert_eq!(
+            table(&CsvOptions::default()),
+            "Базовый интервал;100\n\
+             Время;Частота давления, Гц;T2, Гц\n\
+             00:00:01.500;32768.125000;0.500000\n\
+             Разрыв;00:00:01.600;00:01:01.000\n"
+        );
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            table(&CsvOptions::plain()),
+            "Time,Pressure frequency [Hz],T2 [Hz]\n\
+             1500,32768.125000,0.500000\n\
+             Gap,1600,61000\n"
+        );
+
+        let options = CsvOptions {
+            delimiter: '\t',
+            decimal_comma: true,
+            language: HeaderLanguage::English,
+            precision: 2,
+            metadata: true,
+            timestamp: TimestampStyle::Iso8601(WallClock::new(1_614_834_367_089)),
+            unit_brackets: false,
+        };
+        assert_eq!(
+            table(&options),
+            "Базовый интервал\t100\n\
+             Time\tPressure frequency, Hz\tT2, Hz\n\
+             2021-03-04T05:06:08.589Z\t32768,12\t0,50\n\
+             Gap\t2021-03-04T05:06:08.689Z\t2021-03-04T05:07:08.089Z\n"
+        );
+    }
+}
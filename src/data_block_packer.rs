@@ -1,16 +1,23 @@
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::vec::Vec;
 
 use heatshrink_rust::encoder_to_vec::HeatshrinkEncoderToVec;
 
-use crate::{DataPacketHeader, EmptyBox};
+use crate::{
+    ChannelInfo, DataPacketHeader, EmptyBox, InterleaveSchedule, SampleEncoding,
+    LEGACY_CHANNEL_NAMES,
+};
 
 pub struct DataBlockPacker {
+    /// Заголовок страницы, таблицу каналов после build() менять нельзя
     pub header: DataPacketHeader,
+    header_len: usize,
     encoder: Option<Box<HeatshrinkEncoderToVec>>,
     result: Option<Vec<u8>>,
 }
 
+#[derive(Clone)]
 pub struct DataBlockPackerBuilder {
     header: DataPacketHeader,
     size: usize,
@@ -22,6 +29,10 @@ pub enum PushResult {
     Full,
     Overflow,
     Finished,
+    /// отсчет не записан: в [`crate::SampleEncoding::Delta`] разность с
+    /// предыдущим результатом канала не помещается в i32,
+    /// см. [`crate::SampleEncoder::push()`]
+    OutOfRange,
 }
 
 impl DataBlockPackerBuilder {
@@ -36,17 +47,42 @@ impl DataBlockPackerBuilder {
         self
     }
 
-    pub fn set_targets(mut self, targets: [u32; 2]) -> Self {
-        self.header.targets = targets;
+    /// Таргеты первых N каналов, недостающие каналы добавляются
+    pub fn set_targets<const N: usize>(mut self, targets: [u32; N]) -> Self {
+        self.ensure_channels(N);
+        for (channel, target) in self.header.channels.iter_mut().zip(targets.iter()) {
+            channel.target = *target;
+        }
         self
     }
 
-    pub fn set_write_cfg(mut self, base_interval_ms: u32, interleave_ratio: [u32; 2]) -> Self {
+    /// Базовый интервал и делители первых N каналов, недостающие каналы добавляются
+    pub fn set_write_cfg<const N: usize>(
+        mut self,
+        base_interval_ms: u32,
+        interleave_ratio: [u32; N],
+    ) -> Self {
         self.header.base_interval_ms = base_interval_ms;
-        self.header.interleave_ratio = interleave_ratio;
+        self.ensure_channels(N);
+        for (channel, ratio) in self.header.channels.iter_mut().zip(interleave_ratio.iter()) {
+            channel.interleave_ratio = *ratio;
+        }
+        self
+    }
+
+    /// Заменить таблицу каналов целиком
+    pub fn set_channels(mut self, channels: Vec<ChannelInfo>) -> Self {
+        self.header.channels = channels;
         self
     }
 
+    fn ensure_channels(&mut self, count: usize) {
+        while self.header.channels.len() < count {
+            let name = format!("CH{}", self.header.channels.len());
+            self.header.channels.push(ChannelInfo::new(&name, 0, 1));
+        }
+    }
+
     pub fn set_tcpu(mut self, t_cpu: f32) -> Self {
         self.header.t_cpu = t_cpu;
         self
@@ -62,18 +98,41 @@ impl DataBlockPackerBuilder {
         self
     }
 
+    /// Время старта записи по RTC, мс от 1970-01-01 UTC.
+    /// Страницы с ним пишутся в формате версии 3
+    pub fn set_rtc_start(mut self, rtc_start_ms: u64) -> Self {
+        self.header.rtc_start_ms = Some(rtc_start_ms);
+        self
+    }
+
+    /// Способ записи отсчетов, которым пользуется [`crate::SampleEncoder`]
+    pub fn set_sample_encoding(mut self, sample_encoding: SampleEncoding) -> Self {
+        self.header.sample_encoding = sample_encoding;
+        self
+    }
+
     pub fn set_size(mut self, size: usize) -> Self {
         self.size = size;
         self
     }
 
     pub fn build(self) -> DataBlockPacker {
-        assert!(self.size > core::mem::size_of::<DataPacketHeader>());
+        let header_len = self.header.encoded_len();
+        assert!(self.header.channels.len() <= DataPacketHeader::MAX_CHANNELS);
+        assert!(self
+            .header
+            .channels
+            .iter()
+            .all(|c| c.name.len() <= ChannelInfo::MAX_NAME_LEN));
+        // длина заголовка записывается в u16
+        assert!(header_len <= u16::MAX as usize, "Header too long");
+        assert!(self.size > header_len);
         DataBlockPacker {
             header: self.header,
+            header_len,
             encoder: Some(Box::new(HeatshrinkEncoderToVec::dest(
                 Vec::with_capacity(self.size),
-                core::mem::size_of::<DataPacketHeader>(),
+                header_len,
             ))),
             result: None,
         }
@@ -88,17 +147,21 @@ impl Default for DataBlockPackerBuilder {
                 this_block_id: 0,
 
                 timestamp: 0,
+                rtc_start_ms: None,
                 f_ref: 0.0,
-                targets: [0, 0],
 
                 base_interval_ms: 1000,
-                interleave_ratio: [1, 1],
+                channels: LEGACY_CHANNEL_NAMES
+                    .iter()
+                    .map(|name| ChannelInfo::new(name, 0, 1))
+                    .collect(),
 
                 t_cpu: 0.0,
                 v_bat: 0.0,
 
                 data_len: 0,
                 data_crc32: 0,
+                sample_encoding: SampleEncoding::Delta,
             },
             size: 4096,
         }
@@ -110,6 +173,11 @@ impl DataBlockPacker {
         DataBlockPackerBuilder::default()
     }
 
+    /// Расписание опроса каналов этой страницы
+    pub fn schedule(&self) -> InterleaveSchedule {
+        InterleaveSchedule::for_channels(&self.header.channels)
+    }
+
     fn get_encoder(&mut self) -> Option<&mut HeatshrinkEncoderToVec> {
         if let Some(inner) = self.encoder.as_mut() {
             Some(inner.as_mut())
@@ -165,38 +233,49 @@ impl DataBlockPacker {
         }
     }
 
-    pub fn to_result_trimmed<CrcCalc: FnOnce(&[u8]) -> u32>(
+    /// Завершить страницу досрочно, дальнейшие push вернут PushResult::Finished
+    pub fn finish(&mut self) {
+        if let Some(enc) = self.encoder.take() {
+            let (enc, _) = EmptyBox::take(enc);
+            self.result = Some(enc.result());
+        }
+    }
+
+    /// f - функция расчета CRC32, вызывается для данных и для заголовка
+    pub fn to_result_trimmed<CrcCalc: FnMut(&[u8]) -> u32>(
         mut self,
-        f: CrcCalc,
+        mut f: CrcCalc,
     ) -> Option<Vec<u8>> {
         if let Some(mut d) = self.result {
-            self.header.data_len = (d.len() - core::mem::size_of::<DataPacketHeader>()) as u32;
-            self.header.data_crc32 = f(&d[core::mem::size_of::<DataPacketHeader>()..]);
-            unsafe {
-                core::ptr::copy_nonoverlapping(
-                    &self.header,
-                    d.as_mut_ptr() as *mut DataPacketHeader,
-                    1,
-                )
-            };
+            assert_eq!(
+                self.header.encoded_len(),
+                self.header_len,
+                "Channels table changed after build"
+            );
+            self.header.data_len = (d.len() - self.header_len) as u32;
+            self.header.data_crc32 = f(&d[self.header_len..]);
+            d[..self.header_len].copy_from_slice(&self.header.to_bytes(&mut f));
             Some(d)
         } else {
             None
         }
     }
 
-    pub fn to_result_full<CrcCalc: FnOnce(&[u8]) -> u32>(mut self, f: CrcCalc) -> Option<Vec<u8>> {
+    /// f - функция расчета CRC32, вызывается для данных и для заголовка
+    pub fn to_result_full<CrcCalc: FnMut(&[u8]) -> u32>(
+        mut self,
+        mut f: CrcCalc,
+    ) -> Option<Vec<u8>> {
         if let Some(mut d) = self.result {
-            self.header.data_len = (d.len() - core::mem::size_of::<DataPacketHeader>()) as u32;
-            self.header.data_crc32 = f(&d[core::mem::size_of::<DataPacketHeader>()..]);
-            unsafe {
-                d.set_len(d.capacity());
-                core::ptr::copy_nonoverlapping(
-                    &self.header,
-                    d.as_mut_ptr() as *mut DataPacketHeader,
-                    1,
-                )
-            };
+            assert_eq!(
+                self.header.encoded_len(),
+                self.header_len,
+                "Channels table changed after build"
+            );
+            self.header.data_len = (d.len() - self.header_len) as u32;
+            self.header.data_crc32 = f(&d[self.header_len..]);
+            unsafe { d.set_len(d.capacity()) };
+            d[..self.header_len].copy_from_slice(&self.header.to_bytes(&mut f));
             Some(d)
         } else {
             None
@@ -206,7 +285,7 @@ impl DataBlockPacker {
 
 #[cfg(test)]
 mod tests {
-    use crate::{data_block_packer::PushResult, DataBlockPacker};
+    use crate::{data_block_packer::PushResult, ChannelInfo, DataBlockPacker, PageHeader};
 
     #[test]
     #[should_panic]
@@ -214,6 +293,19 @@ mod tests {
         let _ = DataBlockPacker::builder().set_size(16).build();
     }
 
+    #[test]
+    #[should_panic(expected = "Header too long")]
+    fn create_header_too_long() {
+        // каждый канал занимает в заголовке 9 байт и имя
+        let channels = (0..255)
+            .map(|i| ChannelInfo::new(&format!("{:0>255}", i), 0, 1))
+            .collect();
+        let _ = DataBlockPacker::builder()
+            .set_channels(channels)
+            .set_size(128 * 1024)
+            .build();
+    }
+
     #[test]
     fn crate_push_trimmed() {
         const DATA_SIZE: usize = 4096;
@@ -251,4 +343,63 @@ mod tests {
         let res = packer.to_result_full(|_| 0).unwrap();
         assert_eq!(res.len(), DATA_SIZE);
     }
+
+    #[test]
+    fn channels_from_legacy_setters() {
+        let packer = DataBlockPacker::builder()
+            .set_targets([10, 20, 30])
+            .set_write_cfg(500, [1, 2])
+            .build();
+
+        assert_eq!(packer.header.base_interval_ms, 500);
+        assert_eq!(
+            packer.header.channels,
+            [
+                ChannelInfo::new("FP", 10, 1),
+                ChannelInfo::new("FT", 20, 2),
+                ChannelInfo::new("CH2", 30, 1),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn channels_changed_after_build() {
+        let mut packer = DataBlockPacker::builder().build();
+        for i in 0.. {
+            if packer.push_byte((i & 0xff) as u8) == PushResult::Full {
+                break;
+            }
+        }
+
+        packer.header.channels.push(ChannelInfo::new("X", 1, 1));
+        let _ = packer.to_result_trimmed(|_| 0);
+    }
+
+    #[test]
+    fn header_written_in_wire_layout() {
+        const DATA_SIZE: usize = 4096;
+        let mut packer = DataBlockPacker::builder()
+            .set_ids(41, 42)
+            .set_timestamp(123_456)
+            .set_size(DATA_SIZE)
+            .build();
+
+        for i in 0.. {
+            if packer.push_byte((i & 0xff) as u8) == PushResult::Full {
+                break;
+            }
+        }
+
+        let mut expected = packer.header.clone();
+        let res = packer.to_result_trimmed(|data| data.len() as u32).unwrap();
+        let header_len = expected.encoded_len();
+        expected.data_len = (res.len() - header_len) as u32;
+        expected.data_crc32 = expected.data_len;
+
+        let page_header = PageHeader::parse(&res).unwrap();
+        assert_eq!(page_header.header, expected);
+        assert_eq!(page_header.data_offset, header_len);
+        assert_eq!(page_header.header_crc32, Some((header_len - 4) as u32));
+    }
 }
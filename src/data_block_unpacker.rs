@@ -1,76 +1,292 @@
-use core::mem::MaybeUninit;
-
+use alloc::borrow::Cow;
 use alloc::vec::Vec;
 use heatshrink_rust::decoder::HeatshrinkDecoder;
 
-use crate::DataPacketHeader;
+use crate::{DataPacketHeader, Error, FormatVersion, PageHeader};
+
+/// Результат проверки контрольной суммы части страницы
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ChecksumStatus {
+    /// контрольная сумма совпала
+    Valid,
+    /// контрольная сумма не совпала, данные повреждены
+    Invalid,
+    /// формат страницы не содержит контрольной суммы для этой части
+    Absent,
+}
+
+impl ChecksumStatus {
+    /// Часть страницы не признана поврежденной
+    pub fn is_ok(self) -> bool {
+        self != ChecksumStatus::Invalid
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Значение, которое можно прочитать из распакованных данных
+/// [`DataBlockUnPacker::unpack_as()`]: любой набор байт - допустимое значение.
+/// Реализован только для целых и чисел с плавающей точкой
+pub trait PlainValue: sealed::Sealed + Copy {}
 
-pub struct DataBlockUnPacker {
-    data: Vec<u8>,
+macro_rules! plain_value {
+    ($($t:ty),*) => {
+        $(
+            impl sealed::Sealed for $t {}
+            impl PlainValue for $t {}
+        )*
+    };
 }
 
-impl DataBlockUnPacker {
-    pub fn new(data: Vec<u8>) -> Self {
-        Self { data }
+plain_value!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+/// Целостность заголовка и данных страницы по отдельности
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct PageIntegrity {
+    pub header: ChecksumStatus,
+    pub data: ChecksumStatus,
+}
+
+impl PageIntegrity {
+    pub fn is_ok(&self) -> bool {
+        self.header.is_ok() && self.data.is_ok()
+    }
+}
+
+/// Состояние страницы флеши
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum PageKind {
+    /// стертая страница, все байты 0xFF
+    Erased,
+    /// незаписанная страница, все байты 0x00
+    Blank,
+    /// заголовок и данные не повреждены
+    Valid,
+    /// страница повреждена или не разбирается
+    Corrupt,
+    /// страница записана неизвестной версией формата
+    UnknownVersion,
+}
+
+impl PageKind {
+    /// Стертая или незаписанная страница, по байтам страницы
+    pub fn empty(page: &[u8]) -> Option<PageKind> {
+        match page.first()? {
+            0xFF if page.iter().all(|b| *b == 0xFF) => Some(PageKind::Erased),
+            0x00 if page.iter().all(|b| *b == 0x00) => Some(PageKind::Blank),
+            _ => None,
+        }
+    }
+
+    /// Ошибка, которой распаковка отвечает на пустую страницу
+    pub fn error(self) -> Option<Error> {
+        match self {
+            PageKind::Erased => Some(Error::ErasedPage),
+            PageKind::Blank => Some(Error::BlankPage),
+            _ => None,
+        }
+    }
+}
+
+/// Распаковщик страницы.
+///
+/// Страница передается во владение (`Vec<u8>`) или заимствуется (`&[u8]`),
+/// например прямо из отображенного в память файла: заимствованная страница
+/// не копируется.
+pub struct DataBlockUnPacker<'a> {
+    data: Cow<'a, [u8]>,
+    page_header: Result<PageHeader, Error>,
+}
+
+impl<'a> DataBlockUnPacker<'a> {
+    /// Ошибки разбора заголовка возвращают методы распаковщика
+    pub fn new<D: Into<Cow<'a, [u8]>>>(data: D) -> Self {
+        let data = data.into();
+        let page_header = PageHeader::parse(&data);
+        Self { data, page_header }
+    }
+
+    /// Создать распаковщик, только если буфер вмещает заголовок и сжатые данные
+    pub fn try_new<D: Into<Cow<'a, [u8]>>>(data: D) -> Result<Self, Error> {
+        let res = Self::new(data);
+        res.data_range()?;
+        Ok(res)
+    }
+
+    /// Исходные байты страницы
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Версия формата страницы
+    pub fn version(&self) -> Result<FormatVersion, Error> {
+        self.page_header.as_ref().map(|p| p.version).map_err(|e| *e)
+    }
+
+    /// Заголовок страницы со служебными полями формата
+    pub fn page_header(&self) -> Result<&PageHeader, Error> {
+        self.page_header.as_ref().map_err(|e| *e)
+    }
+
+    pub fn hader(&self) -> Result<DataPacketHeader, Error> {
+        self.page_header().map(|p| p.header.clone())
+    }
+
+    /// Положение сжатых данных в странице
+    fn data_range(&self) -> Result<core::ops::Range<usize>, Error> {
+        let page_header = self.page_header()?;
+        let start = page_header.data_offset;
+        let end = start.saturating_add(page_header.header.data_len as usize);
+
+        if end <= self.data.len() {
+            Ok(start..end)
+        } else {
+            Err(Error::ShortPage {
+                need: end,
+                got: self.data.len(),
+            })
+        }
+    }
+
+    #[cfg(feature = "unpacker")]
+    fn crc32(data: &[u8]) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(data);
+        hasher.finalize()
     }
 
-    pub fn hader(&self) -> DataPacketHeader {
-        let mut res = unsafe { MaybeUninit::zeroed().assume_init() };
+    /// Проверить CRC32 заголовка
+    #[cfg(feature = "unpacker")]
+    pub fn verify_header(&self) -> ChecksumStatus {
+        match self.page_header.as_ref() {
+            Ok(page_header) => match (
+                page_header.header_crc32,
+                page_header.crc_covered(&self.data),
+            ) {
+                (Some(crc), Some(covered)) if Self::crc32(covered) == crc => ChecksumStatus::Valid,
+                (Some(_), _) => ChecksumStatus::Invalid,
+                (None, _) => ChecksumStatus::Absent,
+            },
+            Err(_) => ChecksumStatus::Invalid,
+        }
+    }
 
-        unsafe {
-            core::ptr::copy_nonoverlapping(
-                self.data.as_ptr() as *const DataPacketHeader,
-                &mut res,
-                1,
-            );
+    /// Проверить CRC32 сжатых данных
+    #[cfg(feature = "unpacker")]
+    pub fn verify_data(&self) -> ChecksumStatus {
+        if let Ok(range) = self.data_range() {
+            if self.page_header.as_ref().unwrap().header.data_crc32
+                == Self::crc32(&self.data[range])
+            {
+                return ChecksumStatus::Valid;
+            }
         }
 
-        res
+        ChecksumStatus::Invalid
+    }
+
+    #[cfg(feature = "unpacker")]
+    pub fn integrity(&self) -> PageIntegrity {
+        PageIntegrity {
+            header: self.verify_header(),
+            data: self.verify_data(),
+        }
     }
 
     #[cfg(feature = "unpacker")]
     pub fn verify(&self) -> bool {
-        use crc32fast::Hasher;
+        self.integrity().is_ok()
+    }
 
-        let header = self.hader();
+    /// Определить состояние страницы
+    #[cfg(feature = "unpacker")]
+    pub fn classify(&self) -> PageKind {
+        if let Some(kind) = PageKind::empty(&self.data) {
+            return kind;
+        }
 
-        let checksum = if (header.data_len as usize) < self.data.len() {
-            let mut hasher = Hasher::new();
-            hasher.update(
-                &self.data[core::mem::size_of::<DataPacketHeader>()
-                    ..(core::mem::size_of::<DataPacketHeader>() + header.data_len as usize)],
-            );
-            hasher.finalize()
-        } else {
-            0
-        };
+        match (self.version(), self.check()) {
+            (Err(Error::UnsupportedVersion(_)), _) => PageKind::UnknownVersion,
+            (_, Ok(())) => PageKind::Valid,
+            _ => PageKind::Corrupt,
+        }
+    }
 
-        header.data_crc32 == checksum
+    /// Проверить страницу, вернув первую обнаруженную ошибку
+    #[cfg(feature = "unpacker")]
+    pub fn check(&self) -> Result<(), Error> {
+        self.data_range()?;
+
+        let integrity = self.integrity();
+        if !integrity.header.is_ok() {
+            Err(Error::HeaderCrcMismatch)
+        } else if !integrity.data.is_ok() {
+            Err(Error::DataCrcMismatch)
+        } else {
+            Ok(())
+        }
     }
 
-    pub fn unpack_data(&self) -> Vec<u8> {
-        let header = self.hader();
-        let decoder = HeatshrinkDecoder::source(
-            self.data
+    pub fn unpack_data(&self) -> Result<Vec<u8>, Error> {
+        let range = self.data_range()?;
+        let len = range.len();
+        let consumed = core::cell::Cell::new(0usize);
+
+        let data: Vec<u8> = HeatshrinkDecoder::source(
+            self.data[range]
                 .iter()
-                .skip(core::mem::size_of::<DataPacketHeader>())
-                .take(header.data_len as usize)
+                .inspect(|_| consumed.set(consumed.get() + 1))
                 .cloned(),
-        );
+        )
+        .collect();
+
+        if consumed.get() == len {
+            Ok(data)
+        } else {
+            Err(Error::Decompression {
+                consumed: consumed.get(),
+                len,
+            })
+        }
+    }
+
+    /// Распаковать сколько получится из поврежденной страницы.
+    /// Если `data_len` выходит за конец страницы, сжатыми данными считается
+    /// остаток страницы. Распаковка останавливается после limit байт.
+    /// Возвращает распакованные данные и был ли превышен limit
+    pub fn unpack_data_lossy(&self, limit: usize) -> Result<(Vec<u8>, bool), Error> {
+        let page_header = self.page_header()?;
+        let start = page_header.data_offset.min(self.data.len());
+        let end = start
+            .saturating_add(page_header.header.data_len as usize)
+            .min(self.data.len());
 
-        decoder.collect()
+        let mut data: Vec<u8> = HeatshrinkDecoder::source(self.data[start..end].iter().cloned())
+            .take(limit.saturating_add(1))
+            .collect();
+
+        let overflow = data.len() > limit;
+        data.truncate(limit);
+        Ok((data, overflow))
     }
 
-    pub fn unpack_as<T: Copy + Default>(&self) -> Vec<T> {
-        let data = self.unpack_data();
+    /// Распаковать данные как массив значений T в порядке байт платформы
+    pub fn unpack_as<T: PlainValue>(&self) -> Result<Vec<T>, Error> {
+        let data = self.unpack_data()?;
 
-        assert!(
-            data.len() % core::mem::size_of::<T>() == 0,
-            "Data allignment invalid"
-        );
+        if data.len() % core::mem::size_of::<T>() != 0 {
+            return Err(Error::BadAlignment {
+                len: data.len(),
+                align: core::mem::size_of::<T>(),
+            });
+        }
 
-        data.chunks(core::mem::size_of::<T>())
-            .map(|b| unsafe { *(b.as_ptr() as *const T) })
-            .collect()
+        Ok(data
+            .chunks(core::mem::size_of::<T>())
+            // любой набор байт - допустимое значение PlainValue
+            .map(|b| unsafe { core::ptr::read_unaligned(b.as_ptr() as *const T) })
+            .collect())
     }
 }
@@ -0,0 +1,610 @@
+//! Заголовок страницы и его представление на флешке.
+//!
+//! Заголовок записывается в начало страницы в фиксированной раскладке, не
+//! зависящей ни от компилятора, ни от архитектуры: все поля little-endian,
+//! `f32` хранятся как IEEE-754 биты, выравнивающих промежутков нет.
+//!
+//! Начиная с версии 2 страница начинается с сигнатуры [`PAGE_MAGIC`] и номера
+//! версии формата, по которым декодер выбирает нужный разборщик заголовка.
+//! Страницы версии 1 сигнатуры не имеют и начинаются сразу с полей заголовка.
+//!
+//! Версия 2:
+//!
+//! | Смещение | Размер | Поле                     |
+//! |---------:|-------:|--------------------------|
+//! |        0 |      4 | [`PAGE_MAGIC`]           |
+//! |        4 |      2 | версия формата (2)       |
+//! |        6 |      2 | длина заголовка, байт    |
+//! |        8 |      4 | `prev_block_id`          |
+//! |       12 |      4 | `this_block_id`          |
+//! |       16 |      8 | `timestamp`              |
+//! |       24 |      4 | `f_ref`                  |
+//! |       28 |      4 | `base_interval_ms`       |
+//! |       32 |      4 | `t_cpu`                  |
+//! |       36 |      4 | `v_bat`                  |
+//! |       40 |      4 | `data_len`               |
+//! |       44 |      4 | `data_crc32`             |
+//! |       48 |      1 | `sample_encoding`        |
+//! |       49 |      1 | количество каналов       |
+//! |       50 |      * | описания каналов         |
+//! |        * |      4 | CRC32 заголовка          |
+//!
+//! Описание канала:
+//!
+//! | Смещение | Размер | Поле                     |
+//! |---------:|-------:|--------------------------|
+//! |        0 |      4 | `target`                 |
+//! |        4 |      4 | `interleave_ratio`       |
+//! |        8 |      1 | длина имени, байт        |
+//! |        9 |      * | имя, UTF-8               |
+//!
+//! Это единственная раскладка версии 2: таблица каналов и CRC32 заголовка
+//! входят в неё с первой страницы v2, других вариантов v2 не бывает. Любое
+//! изменение раскладки получает новый номер версии, как версия 3, а старые
+//! версии разбираются по-прежнему.
+//!
+//! Версия 3 отличается от версии 2 только полем времени RTC после `timestamp`,
+//! остальные поля сдвинуты на 8 байт:
+//!
+//! | Смещение | Размер | Поле                     |
+//! |---------:|-------:|--------------------------|
+//! |        4 |      2 | версия формата (3)       |
+//! |       16 |      8 | `timestamp`              |
+//! |       24 |      8 | `rtc_start_ms`           |
+//! |       32 |      4 | `f_ref`                  |
+//! |        … |      … | как в версии 2           |
+//!
+//! Упаковщик пишет страницу версии 3, только если время RTC задано, иначе
+//! версии 2, так что старые декодеры читают такие записи по-прежнему.
+//!
+//! Длина заголовка зависит от числа каналов и длины их имен, см.
+//! [`DataPacketHeader::encoded_len()`]. Сжатые данные начинаются со смещения,
+//! указанного в поле длины заголовка. CRC32 заголовка всегда занимает
+//! последние 4 байта заголовка и считается по всем байтам перед ним, включая
+//! сигнатуру и `data_crc32`, так что повреждение любого поля заголовка
+//! обнаруживается отдельно от повреждения данных.
+//!
+//! Версия 1 (без сигнатуры, ровно 2 канала):
+//!
+//! | Смещение | Размер | Поле                     |
+//! |---------:|-------:|--------------------------|
+//! |        0 |      4 | `prev_block_id`          |
+//! |        4 |      4 | `this_block_id`          |
+//! |        8 |      8 | `timestamp`              |
+//! |       16 |      4 | `f_ref`                  |
+//! |       20 |      8 | `targets[0..2]`          |
+//! |       28 |      4 | `base_interval_ms`       |
+//! |       32 |      8 | `interleave_ratio[0..2]` |
+//! |       40 |      4 | `t_cpu`                  |
+//! |       44 |      4 | `v_bat`                  |
+//! |       48 |      4 | `data_len`               |
+//! |       52 |      4 | `data_crc32`             |
+//!
+//! Итого [`DataPacketHeader::SIZE_V1`] = 56 байт, сразу за ними начинаются сжатые данные.
+//! Каналы страницы v1 получают имена [`LEGACY_CHANNEL_NAMES`], данные страниц v1
+//! всегда закодированы [`SampleEncoding::Delta`].
+//! Если `prev_block_id` страницы v1 случайно совпадёт с сигнатурой, страница
+//! будет принята за v2, на практике номера блоков так далеко не заходят.
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::wire::{Reader, Writer};
+use crate::{Error, SampleEncoding};
+
+/// Сигнатура страницы формата версии 2 и новее
+pub const PAGE_MAGIC: [u8; 4] = *b"SRPK";
+
+/// Имена каналов давления и температуры страниц формата версии 1
+pub const LEGACY_CHANNEL_NAMES: [&str; 2] = ["FP", "FT"];
+
+/// Версия формата страницы
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum FormatVersion {
+    /// Страница без сигнатуры
+    V1 = 1,
+    /// Сигнатура, номер версии, длина заголовка, таблица каналов и CRC32 заголовка
+    V2 = 2,
+    /// Версия 2 и время начала записи по RTC
+    V3 = 3,
+}
+
+impl FormatVersion {
+    /// Версия, в которой упаковщик пишет страницы без времени RTC
+    pub const CURRENT: FormatVersion = FormatVersion::V2;
+
+    /// Определить версию формата по началу страницы
+    pub fn detect(page: &[u8]) -> Result<FormatVersion, Error> {
+        let mut r = Reader::new(page);
+        if r.bytes()? != PAGE_MAGIC {
+            return Ok(FormatVersion::V1);
+        }
+
+        match r.u16()? {
+            2 => Ok(FormatVersion::V2),
+            3 => Ok(FormatVersion::V3),
+            v => Err(Error::UnsupportedVersion(v)),
+        }
+    }
+}
+
+/// Описание канала измерения
+#[derive(PartialEq, Debug, Clone)]
+pub struct ChannelInfo {
+    /// имя канала, не длиннее [`ChannelInfo::MAX_NAME_LEN`] байт
+    pub name: String,
+    /// таргет
+    pub target: u32,
+    /// делитель базового интервала
+    pub interleave_ratio: u32,
+}
+
+impl ChannelInfo {
+    /// Максимальная длина имени канала в UTF-8, байт
+    pub const MAX_NAME_LEN: usize = u8::MAX as usize;
+
+    pub fn new(name: &str, target: u32, interleave_ratio: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            target,
+            interleave_ratio,
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        2 * core::mem::size_of::<u32>() + 1 + self.name.len()
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct DataPacketHeader {
+    /// номер этого блока
+    pub prev_block_id: u32,
+    /// номер предыдущего блока в цепочке
+    pub this_block_id: u32,
+
+    /// таймштамп, время от старта записи
+    pub timestamp: u64,
+    /// время старта записи по RTC, мс от 1970-01-01 UTC, None - RTC не задано.
+    /// Записывается только в страницах версии 3
+    pub rtc_start_ms: Option<u64>,
+    /// опорная частота, она могла меняться между цепочками
+    pub f_ref: f32,
+
+    /// базовый интервал записи, мс
+    pub base_interval_ms: u32,
+    /// каналы в порядке их записи в данные
+    pub channels: Vec<ChannelInfo>,
+
+    /// температура процессора
+    pub t_cpu: f32,
+    /// заряд батареи
+    pub v_bat: f32,
+
+    /// Фактическое количество значащих байт в блоке, не считая еиспользованные с конц байты
+    pub data_len: u32,
+    /// CRC32 (zlib)
+    pub data_crc32: u32,
+    /// способ записи отсчетов в данные
+    pub sample_encoding: SampleEncoding,
+}
+
+/// Разобранный заголовок страницы вместе со служебными полями формата
+#[derive(PartialEq, Debug, Clone)]
+pub struct PageHeader {
+    /// версия формата страницы
+    pub version: FormatVersion,
+    /// смещение сжатых данных от начала страницы
+    pub data_offset: usize,
+    pub header: DataPacketHeader,
+    /// записанная CRC32 заголовка, None - формат не защищает заголовок
+    pub header_crc32: Option<u32>,
+}
+
+impl PageHeader {
+    /// Разобрать начало страницы, выбрав декодер по версии формата
+    pub fn parse(page: &[u8]) -> Result<PageHeader, Error> {
+        match FormatVersion::detect(page)? {
+            FormatVersion::V1 => Ok(PageHeader {
+                version: FormatVersion::V1,
+                data_offset: DataPacketHeader::SIZE_V1,
+                header: DataPacketHeader::read_v1(&mut Reader::new(page))?,
+                header_crc32: None,
+            }),
+            version @ (FormatVersion::V2 | FormatVersion::V3) => {
+                let mut r = Reader::new(page);
+                let _magic: [u8; 4] = r.bytes()?;
+                let _version = r.u16()?;
+                let header_len = r.u16()? as usize;
+                let header = DataPacketHeader::read_v2(&mut r, version == FormatVersion::V3)?;
+
+                if header_len < r.position() + core::mem::size_of::<u32>() {
+                    return Err(Error::InvalidHeaderLength(header_len));
+                }
+                let mut crc_reader = Reader::new(page);
+                crc_reader.slice(header_len - core::mem::size_of::<u32>())?;
+
+                Ok(PageHeader {
+                    version,
+                    data_offset: header_len,
+                    header,
+                    header_crc32: Some(crc_reader.u32()?),
+                })
+            }
+        }
+    }
+
+    /// Часть страницы, по которой считается CRC32 заголовка
+    pub fn crc_covered<'a>(&self, page: &'a [u8]) -> Option<&'a [u8]> {
+        self.header_crc32
+            .map(|_| &page[..self.data_offset - core::mem::size_of::<u32>()])
+    }
+}
+
+impl DataPacketHeader {
+    /// Размер заголовка страницы версии 1, байт
+    pub const SIZE_V1: usize = 56;
+    /// Размер заголовка версии 2 без таблицы каналов, байт
+    pub const FIXED_SIZE: usize = 54;
+    /// Размер заголовка версии 3 без таблицы каналов, байт
+    pub const FIXED_SIZE_V3: usize = Self::FIXED_SIZE + core::mem::size_of::<u64>();
+    /// Максимальное количество каналов
+    pub const MAX_CHANNELS: usize = u8::MAX as usize;
+
+    pub fn is_initial(&self) -> bool {
+        self.prev_block_id == 0 && self.this_block_id == 0
+    }
+
+    /// Версия формата, в которой упаковщик запишет этот заголовок
+    pub fn version(&self) -> FormatVersion {
+        if self.rtc_start_ms.is_some() {
+            FormatVersion::V3
+        } else {
+            FormatVersion::CURRENT
+        }
+    }
+
+    /// Размер заголовка на флешке в версии [`DataPacketHeader::version()`], байт
+    pub fn encoded_len(&self) -> usize {
+        let fixed = match self.version() {
+            FormatVersion::V3 => Self::FIXED_SIZE_V3,
+            _ => Self::FIXED_SIZE,
+        };
+        fixed
+            + self
+                .channels
+                .iter()
+                .map(ChannelInfo::encoded_len)
+                .sum::<usize>()
+    }
+
+    /// Сериализовать заголовок в раскладку версии [`DataPacketHeader::version()`],
+    /// f - функция расчета CRC32 заголовка
+    pub fn to_bytes<CrcCalc: FnOnce(&[u8]) -> u32>(&self, f: CrcCalc) -> Vec<u8> {
+        assert!(self.channels.len() <= Self::MAX_CHANNELS);
+
+        let len = self.encoded_len();
+        assert!(len <= u16::MAX as usize, "Header too long");
+        let crc_offset = len - core::mem::size_of::<u32>();
+        let mut res = vec![0u8; len];
+
+        let mut w = Writer::new(&mut res);
+        w.bytes(&PAGE_MAGIC)
+            .u16(self.version() as u16)
+            .u16(len as u16)
+            .u32(self.prev_block_id)
+            .u32(self.this_block_id)
+            .u64(self.timestamp);
+        if let Some(rtc_start_ms) = self.rtc_start_ms {
+            w.u64(rtc_start_ms);
+        }
+        w.f32(self.f_ref)
+            .u32(self.base_interval_ms)
+            .f32(self.t_cpu)
+            .f32(self.v_bat)
+            .u32(self.data_len)
+            .u32(self.data_crc32)
+            .u8(self.sample_encoding as u8)
+            .u8(self.channels.len() as u8);
+        for channel in self.channels.iter() {
+            assert!(channel.name.len() <= ChannelInfo::MAX_NAME_LEN);
+            w.u32(channel.target)
+                .u32(channel.interleave_ratio)
+                .u8(channel.name.len() as u8)
+                .bytes(channel.name.as_bytes());
+        }
+
+        let crc = f(&res[..crc_offset]);
+        Writer::new(&mut res[crc_offset..]).u32(crc);
+
+        res
+    }
+
+    /// Прочитать заголовок из начала страницы любой поддерживаемой версии
+    pub fn from_bytes(page: &[u8]) -> Result<Self, Error> {
+        PageHeader::parse(page).map(|p| p.header)
+    }
+
+    fn read_v1(r: &mut Reader) -> Result<Self, Error> {
+        let prev_block_id = r.u32()?;
+        let this_block_id = r.u32()?;
+        let timestamp = r.u64()?;
+        let f_ref = r.f32()?;
+        let targets = [r.u32()?, r.u32()?];
+        let base_interval_ms = r.u32()?;
+        let interleave_ratio = [r.u32()?, r.u32()?];
+
+        Ok(Self {
+            prev_block_id,
+            this_block_id,
+            timestamp,
+            rtc_start_ms: None,
+            f_ref,
+            base_interval_ms,
+            channels: LEGACY_CHANNEL_NAMES
+                .iter()
+                .zip(targets.iter().zip(interleave_ratio.iter()))
+                .map(|(name, (target, ratio))| ChannelInfo::new(name, *target, *ratio))
+                .collect(),
+            t_cpu: r.f32()?,
+            v_bat: r.f32()?,
+            data_len: r.u32()?,
+            data_crc32: r.u32()?,
+            sample_encoding: SampleEncoding::Delta,
+        })
+    }
+
+    /// Заголовок версии 2, rtc - есть поле времени RTC версии 3
+    fn read_v2(r: &mut Reader, rtc: bool) -> Result<Self, Error> {
+        let mut res = Self {
+            prev_block_id: r.u32()?,
+            this_block_id: r.u32()?,
+            timestamp: r.u64()?,
+            rtc_start_ms: if rtc { Some(r.u64()?) } else { None },
+            f_ref: r.f32()?,
+            base_interval_ms: r.u32()?,
+            channels: Vec::new(),
+            t_cpu: r.f32()?,
+            v_bat: r.f32()?,
+            data_len: r.u32()?,
+            data_crc32: r.u32()?,
+            sample_encoding: SampleEncoding::from_u8(r.u8()?)?,
+        };
+
+        let count = r.u8()?;
+        for _ in 0..count {
+            let target = r.u32()?;
+            let interleave_ratio = r.u32()?;
+            let name_len = r.u8()? as usize;
+            res.channels.push(ChannelInfo {
+                name: String::from_utf8_lossy(r.slice(name_len)?).into_owned(),
+                target,
+                interleave_ratio,
+            });
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::{
+        ChannelInfo, DataPacketHeader, FormatVersion, PageHeader, LEGACY_CHANNEL_NAMES, PAGE_MAGIC,
+    };
+    use crate::{Error, SampleEncoding};
+
+    fn known_header() -> DataPacketHeader {
+        DataPacketHeader {
+            prev_block_id: 0x0403_0201,
+            this_block_id: 0x0807_0605,
+            timestamp: 0x1817_1615_1413_1211,
+            rtc_start_ms: None,
+            f_ref: 1.0,
+            base_interval_ms: 1000,
+            channels: vec![
+                ChannelInfo::new(LEGACY_CHANNEL_NAMES[0], 0x2423_2221, 3),
+                ChannelInfo::new(LEGACY_CHANNEL_NAMES[1], 0x2827_2625, 5),
+            ],
+            t_cpu: -2.5,
+            v_bat: 3.3,
+            data_len: 0x0000_0FC8,
+            data_crc32: 0xDEAD_BEEF,
+            sample_encoding: SampleEncoding::Delta,
+        }
+    }
+
+    fn known_header_3ch() -> DataPacketHeader {
+        let mut res = known_header();
+        res.channels.push(ChannelInfo::new("ACC", 7, 1));
+        res
+    }
+
+    #[rustfmt::skip]
+    const KNOWN_V1_IMAGE: [u8; DataPacketHeader::SIZE_V1] = [
+        0x01, 0x02, 0x03, 0x04, // prev_block_id
+        0x05, 0x06, 0x07, 0x08, // this_block_id
+        0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, // timestamp
+        0x00, 0x00, 0x80, 0x3F, // f_ref = 1.0
+        0x21, 0x22, 0x23, 0x24, // targets[0]
+        0x25, 0x26, 0x27, 0x28, // targets[1]
+        0xE8, 0x03, 0x00, 0x00, // base_interval_ms = 1000
+        0x03, 0x00, 0x00, 0x00, // interleave_ratio[0]
+        0x05, 0x00, 0x00, 0x00, // interleave_ratio[1]
+        0x00, 0x00, 0x20, 0xC0, // t_cpu = -2.5
+        0x33, 0x33, 0x53, 0x40, // v_bat = 3.3
+        0xC8, 0x0F, 0x00, 0x00, // data_len
+        0xEF, 0xBE, 0xAD, 0xDE, // data_crc32
+    ];
+
+    /// Полная раскладка версии 2, см. таблицу в начале модуля
+    #[rustfmt::skip]
+    const KNOWN_IMAGE: [u8; 88] = [
+        b'S', b'R', b'P', b'K', // magic
+        0x02, 0x00, // version
+        0x58, 0x00, // header length = 88
+        0x01, 0x02, 0x03, 0x04, // prev_block_id
+        0x05, 0x06, 0x07, 0x08, // this_block_id
+        0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, // timestamp
+        0x00, 0x00, 0x80, 0x3F, // f_ref = 1.0
+        0xE8, 0x03, 0x00, 0x00, // base_interval_ms = 1000
+        0x00, 0x00, 0x20, 0xC0, // t_cpu = -2.5
+        0x33, 0x33, 0x53, 0x40, // v_bat = 3.3
+        0xC8, 0x0F, 0x00, 0x00, // data_len
+        0xEF, 0xBE, 0xAD, 0xDE, // data_crc32
+        0x00, // sample_encoding
+        0x03, // channels count
+        0x21, 0x22, 0x23, 0x24, 0x03, 0x00, 0x00, 0x00, 0x02, b'F', b'P',
+        0x25, 0x26, 0x27, 0x28, 0x05, 0x00, 0x00, 0x00, 0x02, b'F', b'T',
+        0x07, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, b'A', b'C', b'C',
+        0x12, 0x34, 0x56, 0x78, // header CRC32
+    ];
+
+    const KNOWN_HEADER_CRC: u32 = 0x7856_3412;
+
+    #[test]
+    fn to_bytes_layout() {
+        let header = known_header_3ch();
+        assert_eq!(header.encoded_len(), KNOWN_IMAGE.len());
+
+        let image = header.to_bytes(|covered| {
+            assert_eq!(covered, &KNOWN_IMAGE[..KNOWN_IMAGE.len() - 4]);
+            KNOWN_HEADER_CRC
+        });
+        assert_eq!(&image[..], &KNOWN_IMAGE[..]);
+    }
+
+    #[test]
+    fn from_bytes_layout() {
+        let parsed = PageHeader::parse(&KNOWN_IMAGE).unwrap();
+        assert_eq!(parsed.version, FormatVersion::V2);
+        assert_eq!(parsed.data_offset, KNOWN_IMAGE.len());
+        assert_eq!(parsed.header, known_header_3ch());
+        assert_eq!(parsed.header_crc32, Some(KNOWN_HEADER_CRC));
+        assert_eq!(
+            parsed.crc_covered(&KNOWN_IMAGE),
+            Some(&KNOWN_IMAGE[..KNOWN_IMAGE.len() - 4])
+        );
+    }
+
+    #[test]
+    fn from_bytes_v1_layout() {
+        let parsed = PageHeader::parse(&KNOWN_V1_IMAGE).unwrap();
+        assert_eq!(parsed.version, FormatVersion::V1);
+        assert_eq!(parsed.data_offset, DataPacketHeader::SIZE_V1);
+        assert_eq!(parsed.header, known_header());
+        assert_eq!(parsed.header_crc32, None);
+        assert_eq!(parsed.crc_covered(&KNOWN_V1_IMAGE), None);
+    }
+
+    #[test]
+    fn unknown_version() {
+        let mut image = KNOWN_IMAGE;
+        image[PAGE_MAGIC.len()] = 0x7F;
+
+        assert_eq!(
+            FormatVersion::detect(&image),
+            Err(Error::UnsupportedVersion(0x7F))
+        );
+        assert_eq!(
+            DataPacketHeader::from_bytes(&image),
+            Err(Error::UnsupportedVersion(0x7F))
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut header = known_header_3ch();
+        header.f_ref = f32::NAN;
+        header.channels[2].name = "ускорение".into();
+
+        let decoded = DataPacketHeader::from_bytes(&header.to_bytes(|_| 0)).unwrap();
+        assert_eq!(decoded.f_ref.to_bits(), header.f_ref.to_bits());
+        assert_eq!(decoded.channels, header.channels);
+        assert_eq!(decoded.to_bytes(|_| 0), header.to_bytes(|_| 0));
+    }
+
+    #[test]
+    fn rtc_start_layout() {
+        let mut header = known_header_3ch();
+        header.rtc_start_ms = Some(0x2827_2625_2423_2221);
+        assert_eq!(header.version(), FormatVersion::V3);
+        assert_eq!(header.encoded_len(), KNOWN_IMAGE.len() + 8);
+
+        // то же, что в версии 2, со вставкой времени RTC после timestamp
+        let mut expected = KNOWN_IMAGE[..24].to_vec();
+        expected[4] = 0x03;
+        expected[6] = 0x60;
+        expected.extend_from_slice(&[0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28]);
+        expected.extend_from_slice(&KNOWN_IMAGE[24..KNOWN_IMAGE.len() - 4]);
+
+        let image = header.to_bytes(|covered| {
+            assert_eq!(covered, &expected[..]);
+            KNOWN_HEADER_CRC
+        });
+        let parsed = PageHeader::parse(&image).unwrap();
+        assert_eq!(parsed.version, FormatVersion::V3);
+        assert_eq!(parsed.data_offset, image.len());
+        assert_eq!(parsed.header, header);
+        assert_eq!(parsed.header_crc32, Some(KNOWN_HEADER_CRC));
+    }
+
+    #[test]
+    fn truncated_header() {
+        assert_eq!(
+            PageHeader::parse(&KNOWN_IMAGE[..3]),
+            Err(Error::ShortPage { need: 4, got: 3 })
+        );
+        assert_eq!(
+            PageHeader::parse(&KNOWN_IMAGE[..60]),
+            Err(Error::ShortPage { need: 61, got: 60 })
+        );
+        assert_eq!(
+            PageHeader::parse(&KNOWN_IMAGE[..KNOWN_IMAGE.len() - 1]),
+            Err(Error::ShortPage {
+                need: KNOWN_IMAGE.len(),
+                got: KNOWN_IMAGE.len() - 1
+            })
+        );
+        assert_eq!(
+            PageHeader::parse(&KNOWN_V1_IMAGE[..50]),
+            Err(Error::ShortPage { need: 52, got: 50 })
+        );
+    }
+
+    #[test]
+    fn header_length_too_small() {
+        let mut image = KNOWN_IMAGE;
+        image[6] = 0x20;
+
+        assert_eq!(
+            PageHeader::parse(&image),
+            Err(Error::InvalidHeaderLength(0x20))
+        );
+    }
+
+    #[test]
+    fn unknown_sample_encoding() {
+        let mut image = KNOWN_IMAGE;
+        image[48] = 0x42;
+
+        assert_eq!(
+            PageHeader::parse(&image),
+            Err(Error::UnsupportedSampleEncoding(0x42))
+        );
+    }
+
+    #[test]
+    fn no_channels() {
+        let mut header = known_header();
+        header.channels = Vec::new();
+
+        let image = header.to_bytes(|_| 0);
+        assert_eq!(image.len(), DataPacketHeader::FIXED_SIZE);
+        assert_eq!(DataPacketHeader::from_bytes(&image), Ok(header));
+    }
+}
@@ -1,20 +1,43 @@
-use std::{fmt::Display, fs::File, io::Write, path::Path, time::Duration};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
 
 use alloc::vec::Vec;
 
-use crate::{DataBlockUnPacker, DataPacketHeader};
+use crate::csv_export::CsvWriter;
+use crate::{
+    ChannelInfo, CsvOptions, DataBlockUnPacker, DataPacketHeader, Error, Fref, InterleaveSchedule,
+    PageIntegrity, PageKind, SampleDecoder,
+};
 
 #[derive(Clone, Copy, Default)]
 pub struct Record {
     pub timesstamp: u64,
     pub freq: f32,
+    /// результат счетчика, по которому вычислена freq
+    pub result: u32,
 }
 
 pub struct PageData {
     pub header: DataPacketHeader,
+    /// заголовок и данные не повреждены
     pub consistant: bool,
-    pub fp: Vec<Record>,
-    pub ft: Vec<Record>,
+    /// целостность заголовка и данных по отдельности
+    pub integrity: PageIntegrity,
+    /// опорная частота, по которой вычислены частоты каналов
+    pub fref: Fref,
+    /// каналы в порядке их описания в заголовке
+    pub channels: Vec<ChannelData>,
+}
+
+/// Результаты одного канала страницы
+pub struct ChannelData {
+    pub info: ChannelInfo,
+    pub records: Vec<Record>,
 }
 
 pub struct PrettyDuration(pub Duration);
@@ -51,92 +74,130 @@ impl Display for PrettyDuration {
     }
 }
 
+impl PageKind {
+    /// Состояние страницы по результату её распаковки, например из [`unpack_pages()`]
+    pub fn of(page: &Result<PageData, Error>) -> PageKind {
+        match page {
+            Ok(page) if page.integrity.is_ok() => PageKind::Valid,
+            Ok(_) => PageKind::Corrupt,
+            Err(Error::ErasedPage) => PageKind::Erased,
+            Err(Error::BlankPage) => PageKind::Blank,
+            Err(Error::UnsupportedVersion(_)) => PageKind::UnknownVersion,
+            Err(_) => PageKind::Corrupt,
+        }
+    }
+}
+
 impl PageData {
+    /// Страница с пустыми каналами, fref_base - опорная частота из настроек
+    pub(crate) fn without_records(
+        header: DataPacketHeader,
+        integrity: PageIntegrity,
+        fref_base: f32,
+    ) -> Self {
+        Self {
+            fref: Fref::resolve(&header, fref_base),
+            consistant: integrity.is_ok(),
+            integrity,
+            channels: header
+                .channels
+                .iter()
+                .map(|info| ChannelData {
+                    info: info.clone(),
+                    records: Vec::new(),
+                })
+                .collect(),
+            header,
+        }
+    }
+
     pub fn save_as_csv<P: AsRef<Path>>(&self, file: P) -> std::io::Result<()> {
-        let mut file = File::create(file)?;
+        self.save_as_csv_with(file, &CsvOptions::default())
+    }
 
-        if self.header.is_initial() {
-            file.write_fmt(format_args!(
-                "Стартовая страница;{}\n",
-                self.header.this_block_id
-            ))?;
+    /// Сохранить страницу в CSV с заданными настройками
+    pub fn save_as_csv_with<P: AsRef<Path>>(
+        &self,
+        file: P,
+        options: &CsvOptions,
+    ) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(file)?);
+        self.write_csv(&mut file, options)?;
+        file.flush()
+    }
+
+    /// Записать страницу в CSV с заданными настройками в любой приемник,
+    /// например в буфер, сокет или stdout
+    pub fn write_csv<W: Write>(&self, w: W, options: &CsvOptions) -> std::io::Result<()> {
+        let mut w = CsvWriter::new(w, options);
+        let h = &self.header;
+
+        if h.is_initial() {
+            w.meta([
+                options.tr("Стартовая страница", "Initial page").to_string(),
+                h.this_block_id.to_string(),
+            ])?;
         } else {
-            file.write_fmt(format_args!(
-                "Страница {};предыдущая {}\n",
-                self.header.this_block_id, self.header.prev_block_id
-            ))?;
+            w.meta([
+                format!("{} {}", options.tr("Страница", "Page"), h.this_block_id),
+                format!(
+                    "{} {}",
+                    options.tr("предыдущая", "previous"),
+                    h.prev_block_id
+                ),
+            ])?;
         }
 
-        let start = Duration::from_millis(self.header.timestamp);
-        file.write_fmt(format_args!(
-            "Время начала страницы;{}\n",
-            PrettyDuration(start)
-        ))?;
-        file.write_fmt(format_args!(
-            "Базовый интервал;{};мс.\n",
-            self.header.base_interval_ms
-        ))?;
-        file.write_fmt(format_args!(
-            "Температура процессора;{};*С.;Заряд батареи;{};В\n",
-            self.header.t_cpu, self.header.v_bat
-        ))?;
-
-        file.write("Время;Частота давления, Гц;Частота температуры, Гц\n".as_bytes())?;
-
-        {
-            let mut p_iter = self.fp.iter();
-            let mut t_iter = self.ft.iter();
-            let mut c_fp = if let Some(fp) = p_iter.next() {
-                *fp
-            } else {
-                Record::default()
-            };
-            let mut c_ft = if let Some(ft) = t_iter.next() {
-                *ft
-            } else {
-                Record::default()
-            };
-
-            file.write_fmt(format_args!(
-                "{};{:.6};{:.6}\n",
-                PrettyDuration(Duration::from_millis(self.header.timestamp)),
-                c_fp.freq,
-                c_ft.freq
-            ))?;
-
-            for i in 1.. {
-                let timstamp = Duration::from_millis(
-                    self.header.timestamp + (i * self.header.base_interval_ms) as u64,
-                );
-                let mut has_result = false;
-
-                if i % self.header.interleave_ratio[0] == 0 {
-                    if let Some(fp) = p_iter.next() {
-                        c_fp = *fp;
-                        has_result |= true;
-                    } else {
-                        break;
-                    }
-                }
+        w.meta([
+            options
+                .tr("Время начала страницы", "Page start time")
+                .to_string(),
+            options.format_timestamp(h.timestamp),
+        ])?;
+        w.meta([
+            options.tr("Базовый интервал", "Base interval").to_string(),
+            h.base_interval_ms.to_string(),
+            options.tr("мс.", "ms").to_string(),
+        ])?;
+        w.meta([
+            options
+                .tr("Температура процессора", "CPU temperature")
+                .to_string(),
+            h.t_cpu.to_string(),
+            options.tr("*С.", "*C").to_string(),
+            options.tr("Заряд батареи", "Battery voltage").to_string(),
+            h.v_bat.to_string(),
+            options.tr("В", "V").to_string(),
+        ])?;
 
-                if i % self.header.interleave_ratio[1] == 0 {
-                    if let Some(ft) = t_iter.next() {
-                        c_ft = *ft;
-                        has_result |= true;
-                    } else {
-                        break;
-                    }
-                }
+        let channels = self
+            .channels
+            .iter()
+            .map(|c| c.info.clone())
+            .collect::<Vec<_>>();
+        w.header(&channels)?;
 
-                if has_result {
-                    file.write_fmt(format_args!(
-                        "{};{:.6};{:.6}\n",
-                        PrettyDuration(timstamp),
-                        c_fp.freq,
-                        c_ft.freq
-                    ))?
+        // значение канала держится до его следующего отсчета
+        let mut next = vec![0usize; self.channels.len()];
+        let mut current = vec![Record::default(); self.channels.len()];
+        for tick in InterleaveSchedule::for_channels(&h.channels) {
+            let mut has_result = false;
+            for ch in tick.channels {
+                if let Some(r) = self.channels[ch].records.get(next[ch]) {
+                    current[ch] = *r;
+                    next[ch] += 1;
+                    has_result = true;
                 }
             }
+
+            if !has_result {
+                break;
+            }
+
+            w.row(
+                h.timestamp + tick.tick * h.base_interval_ms as u64,
+                &current,
+            )?;
         }
 
         Ok(())
@@ -151,92 +212,88 @@ pub fn calc_f(target: u32, result: u32, fref: f32) -> f32 {
 /// page_size - размер страницы
 /// fref - опорная частота из настроек
 /// ignore_inconsistant - игнорировать ошибки и продлолжать
+///
+/// Результат по каждой странице в порядке их следования: страницы, которые не
+/// удалось разобрать или распаковать, дают ошибку и не прерывают остальные.
+/// Неполная последняя страница тоже входит в результат, её размер -
+/// [`truncated_tail()`].
+/// Стертые и незаписанные страницы дают [`Error::ErasedPage`] и
+/// [`Error::BlankPage`], см. [`PageKind::of()`].
+/// Страница с несовпавшей CRC32 без ignore_inconsistant возвращается без
+/// результатов с consistant = false.
+///
+/// Паникует, если page_size = 0
 pub fn unpack_pages(
     data: &[u8],
     page_size: usize,
     fref_base: f32,
     ignore_inconsistant: bool,
-) -> Vec<PageData> {
-    use crate::add_signed::AddSigned;
-    use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
-
-    let data = if data.len() % page_size == 0 {
-        data
-    } else {
-        let full_pages = data.len() / page_size;
-        println!(
-            "Warning! allignment error: data size {} % page size {} != 0",
-            data.len(),
-            page_size
-        );
-        &data[..full_pages * page_size]
-    };
-
-    data.into_par_iter()
-        .chunks(page_size)
-        .map(|page| {
-            let unpacker = DataBlockUnPacker::new(page.into_iter().cloned().collect());
-            let mut result = PageData {
-                header: unpacker.hader(),
-                consistant: unpacker.verify(),
-                fp: Vec::new(),
-                ft: Vec::new(),
-            };
-
-            let fref = if result.header.f_ref.is_normal() {
-                result.header.f_ref
+) -> Vec<Result<PageData, Error>> {
+    use rayon::{iter::ParallelIterator, slice::ParallelSlice};
+
+    assert!(page_size > 0, "Page size must not be zero");
+    // неполная последняя страница разбирается как остальные: если её сжатые
+    // данные уцелели, она распакуется, иначе даст ошибку
+    data.par_chunks(page_size)
+        .map(|page| unpack_page(page, fref_base, ignore_inconsistant))
+        .collect()
+}
+
+/// Размер неполной последней страницы в данных длиной len,
+/// None - данные делятся на страницы без остатка.
+///
+/// Паникует, если page_size = 0
+pub fn truncated_tail(len: usize, page_size: usize) -> Option<usize> {
+    assert!(page_size > 0, "Page size must not be zero");
+    match len % page_size {
+        0 => None,
+        tail => Some(tail),
+    }
+}
+
+pub(crate) fn unpack_page(
+    page: &[u8],
+    fref_base: f32,
+    ignore_inconsistant: bool,
+) -> Result<PageData, Error> {
+    if let Some(e) = PageKind::empty(page).and_then(PageKind::error) {
+        return Err(e);
+    }
+
+    let unpacker = DataBlockUnPacker::try_new(page)?;
+    let mut result = PageData::without_records(unpacker.hader()?, unpacker.integrity(), fref_base);
+
+    if !(ignore_inconsistant || result.consistant) || result.channels.is_empty() {
+        return Ok(result);
+    }
+
+    let fref = result.fref.value;
+
+    // unpack data
+    let data = unpacker.unpack_data()?;
+    let mut decoder =
+        SampleDecoder::new(&data, result.header.sample_encoding, result.channels.len())?;
+
+    let schedule = InterleaveSchedule::for_channels(&result.header.channels);
+    if schedule.is_idle() && !decoder.is_empty() {
+        return Err(Error::ZeroInterleave);
+    }
+
+    'decode: for tick in schedule {
+        for ch in tick.channels {
+            if let Some(v) = decoder.next_result(ch)? {
+                let channel = &mut result.channels[ch];
+                channel.records.push(Record {
+                    freq: calc_f(channel.info.target, v, fref),
+                    timesstamp: result.header.timestamp
+                        + tick.tick * result.header.base_interval_ms as u64,
+                    result: v,
+                });
             } else {
-                fref_base
-            };
-
-            if ignore_inconsistant || result.consistant {
-                // unpack data
-                let mut data_iter = unpacker.unpack_as::<u32>().into_iter();
-
-                let mut prev_p = 0u32;
-                let mut prev_t = 0u32;
-                for i in 0u32.. {
-                    if result.header.interleave_ratio[0] == 0
-                        || result.header.interleave_ratio[1] == 0
-                    {
-                        break;
-                    }
-                    if i % result.header.interleave_ratio[0] == 0 {
-                        if let Some(v) = data_iter.next() {
-                            let this_value = prev_p
-                                .my_checked_add_signed(unsafe { core::mem::transmute(v) })
-                                .unwrap_or_default();
-                            prev_p = this_value;
-                            result.fp.push(Record {
-                                freq: calc_f(result.header.targets[0], this_value, fref),
-                                timesstamp: result.header.timestamp
-                                    + (i * result.header.base_interval_ms) as u64,
-                            });
-                        } else {
-                            break;
-                        }
-                    }
-                    if (result.header.interleave_ratio[1] != 0)
-                        && (i % result.header.interleave_ratio[1] == 0)
-                    {
-                        if let Some(v) = data_iter.next() {
-                            let this_value = prev_t
-                                .my_checked_add_signed(unsafe { core::mem::transmute(v) })
-                                .unwrap_or_default();
-                            prev_t = this_value;
-                            result.ft.push(Record {
-                                freq: calc_f(result.header.targets[1], this_value, fref),
-                                timesstamp: result.header.timestamp
-                                    + (i * result.header.base_interval_ms) as u64,
-                            });
-                        } else {
-                            break;
-                        }
-                    }
-                }
+                break 'decode;
             }
+        }
+    }
 
-            result
-        })
-        .collect()
+    Ok(result)
 }
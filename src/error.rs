@@ -0,0 +1,110 @@
+use core::fmt;
+
+/// Ошибки разбора и распаковки страницы и записи цепочки страниц
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Error {
+    /// Страница короче, чем требует её содержимое
+    ShortPage {
+        /// сколько байт требуется
+        need: usize,
+        /// сколько байт есть
+        got: usize,
+    },
+    /// Страница стерта: все байты 0xFF
+    ErasedPage,
+    /// Страница не записывалась: все байты 0x00
+    BlankPage,
+    /// Страница записана неизвестной версией формата
+    UnsupportedVersion(u16),
+    /// Данные страницы закодированы неизвестным способом
+    UnsupportedSampleEncoding(u8),
+    /// Длина заголовка не согласуется с его содержимым
+    InvalidHeaderLength(usize),
+    /// Длина распакованных данных не кратна размеру значения
+    BadAlignment {
+        /// длина распакованных данных, байт
+        len: usize,
+        /// размер значения, байт
+        align: usize,
+    },
+    /// Не совпала CRC32 заголовка
+    HeaderCrcMismatch,
+    /// Не совпала CRC32 сжатых данных
+    DataCrcMismatch,
+    /// Восстановленный по разности результат вышел за пределы u32
+    DeltaOverflow {
+        /// номер канала
+        channel: usize,
+        /// номер отсчета в канале
+        sample: usize,
+    },
+    /// Отсчет в данных оборван или закодирован неверно
+    MalformedSample {
+        /// номер канала
+        channel: usize,
+        /// номер отсчета в канале
+        sample: usize,
+    },
+    /// В странице есть данные, но у всех каналов нулевой делитель базового интервала
+    ZeroInterleave,
+    /// Распаковщик остановился, не обработав сжатые данные до конца
+    Decompression {
+        /// сколько байт сжатых данных обработано
+        consumed: usize,
+        /// сколько байт сжатых данных в странице
+        len: usize,
+    },
+    /// Результат канала не записать: в [`crate::SampleEncoding::Delta`] разность
+    /// с предыдущим результатом канала не помещается в i32,
+    /// см. [`crate::ChainRecorder::push()`]
+    ResultOutOfRange {
+        /// номер канала
+        channel: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ShortPage { need, got } => {
+                write!(f, "page too short: need {} bytes, got {}", need, got)
+            }
+            Error::ErasedPage => write!(f, "erased page"),
+            Error::BlankPage => write!(f, "blank page"),
+            Error::UnsupportedVersion(v) => write!(f, "unsupported page format version {}", v),
+            Error::UnsupportedSampleEncoding(v) => {
+                write!(f, "unsupported sample encoding {}", v)
+            }
+            Error::InvalidHeaderLength(len) => write!(f, "invalid header length {}", len),
+            Error::BadAlignment { len, align } => write!(
+                f,
+                "unpacked data size {} is not a multiple of {}",
+                len, align
+            ),
+            Error::HeaderCrcMismatch => write!(f, "header CRC32 mismatch"),
+            Error::DataCrcMismatch => write!(f, "data CRC32 mismatch"),
+            Error::DeltaOverflow { channel, sample } => write!(
+                f,
+                "channel {} sample {}: delta overflows the counter result",
+                channel, sample
+            ),
+            Error::MalformedSample { channel, sample } => {
+                write!(f, "channel {} sample {}: malformed sample", channel, sample)
+            }
+            Error::ZeroInterleave => write!(f, "data present but no channel is sampled"),
+            Error::Decompression { consumed, len } => write!(
+                f,
+                "decompression stopped after {} of {} bytes",
+                consumed, len
+            ),
+            Error::ResultOutOfRange { channel } => write!(
+                f,
+                "channel {}: result step does not fit the sample encoding",
+                channel
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "unpacker")]
+impl std::error::Error for Error {}
@@ -0,0 +1,290 @@
+//! Опорная частота страниц.
+//!
+//! Частоты каналов вычисляются по опорной частоте из заголовка страницы, а
+//! если её там нет - по частоте из настроек распаковки. Какая частота
+//! использована и откуда она взята, записывается в [`PageData::fref`].
+//! Опорная частота может меняться между цепочками; если в заголовках она
+//! неверна или не записана, её можно задать для всего сеанса таблицей
+//! [`FrefOverrides`], частоты страниц сеанса пересчитываются по результатам
+//! счетчика [`crate::Record::result`].
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use crate::data_unpacker::calc_f;
+use crate::{Chain, Chains, DataPacketHeader, PageData};
+
+/// Откуда взята опорная частота страницы
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum FrefSource {
+    /// записана в заголовке страницы
+    Header,
+    /// задана для сеанса, см. [`FrefOverrides`]
+    Override,
+    /// в заголовке нет, взята из настроек распаковки
+    Default,
+}
+
+/// Опорная частота, по которой вычислены частоты страницы
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Fref {
+    /// Гц
+    pub value: f32,
+    pub source: FrefSource,
+}
+
+impl Fref {
+    /// Опорная частота страницы: из заголовка, если она там записана, иначе fref_base
+    pub fn resolve(header: &DataPacketHeader, fref_base: f32) -> Self {
+        match recorded(header) {
+            Some(value) => Fref {
+                value,
+                source: FrefSource::Header,
+            },
+            None => Fref {
+                value: fref_base,
+                source: FrefSource::Default,
+            },
+        }
+    }
+}
+
+/// Опорная частота из заголовка, None - не записана
+fn recorded(header: &DataPacketHeader) -> Option<f32> {
+    if header.f_ref.is_normal() {
+        Some(header.f_ref)
+    } else {
+        None
+    }
+}
+
+/// Опорные частоты сеансов, заменяющие частоты из заголовков и настроек
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FrefOverrides {
+    /// номер сеанса в [`Chains::sessions`] - опорная частота, Гц
+    pub sessions: BTreeMap<usize, f32>,
+}
+
+impl FrefOverrides {
+    /// Задать опорную частоту сеанса
+    pub fn set(mut self, session: usize, fref: f32) -> Self {
+        self.sessions.insert(session, fref);
+        self
+    }
+}
+
+/// Опорная частота в заголовке страницы page отличается от предыдущей
+/// страницы цепочки
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct FrefChange {
+    /// номер страницы в цепочке
+    pub page: usize,
+    /// частота предыдущей страницы, Гц, None - не записана
+    pub previous: Option<f32>,
+    /// частота страницы, Гц, None - не записана
+    pub actual: Option<f32>,
+}
+
+impl Display for FrefChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hz = |v: Option<f32>| match v {
+            Some(v) => format!("{} Гц", v),
+            None => "не записана".to_string(),
+        };
+        write!(
+            f,
+            "Страница {}: опорная частота {}, на предыдущей {}",
+            self.page,
+            hz(self.actual),
+            hz(self.previous)
+        )
+    }
+}
+
+/// Найти страницы цепочки, опорная частота в заголовке которых отличается
+/// от предыдущей страницы
+pub fn fref_changes(pages: &[PageData]) -> Vec<FrefChange> {
+    pages
+        .windows(2)
+        .enumerate()
+        .filter_map(|(i, pair)| {
+            let previous = recorded(&pair[0].header);
+            let actual = recorded(&pair[1].header);
+            if previous != actual {
+                Some(FrefChange {
+                    page: i + 1,
+                    previous,
+                    actual,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+impl PageData {
+    /// Пересчитать частоты страницы по опорной частоте fref
+    pub fn override_fref(&mut self, fref: f32) {
+        for channel in self.channels.iter_mut() {
+            let target = channel.info.target;
+            for r in channel.records.iter_mut() {
+                r.freq = calc_f(target, r.result, fref);
+            }
+        }
+        self.fref = Fref {
+            value: fref,
+            source: FrefSource::Override,
+        };
+    }
+}
+
+impl Chain {
+    /// Опорная частота в заголовках соседних страниц цепочки различается
+    pub fn fref_changes(&self) -> Vec<FrefChange> {
+        fref_changes(&self.pages)
+    }
+
+    /// Пересчитать частоты всех страниц цепочки по опорной частоте fref
+    pub fn override_fref(&mut self, fref: f32) {
+        for page in self.pages.iter_mut() {
+            page.override_fref(fref);
+        }
+    }
+}
+
+impl Chains {
+    /// Заменить опорные частоты сеансов по таблице. Сеансы, которых нет,
+    /// пропускаются
+    pub fn override_fref(&mut self, overrides: &FrefOverrides) {
+        for (session, fref) in overrides.sessions.iter() {
+            if let Some(chain) = self.sessions.get_mut(*session) {
+                chain.override_fref(*fref);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fref_changes, Fref, FrefChange, FrefOverrides, FrefSource};
+    use crate::data_unpacker::calc_f;
+    use crate::{
+        Chain, Chains, ChannelData, ChannelInfo, ChecksumStatus, DataPacketHeader, PageData,
+        PageIntegrity, Record, SampleEncoding,
+    };
+
+    /// Страница с одним результатом счетчика канала FP
+    fn page(this: u32, f_ref: f32, fref_base: f32, result: u32) -> PageData {
+        let info = ChannelInfo::new("FP", 1, 1);
+        let header = DataPacketHeader {
+            prev_block_id: this.wrapping_sub(1),
+            this_block_id: this,
+            timestamp: 0,
+            rtc_start_ms: None,
+            f_ref,
+            base_interval_ms: 100,
+            channels: vec![info.clone()],
+            t_cpu: 0.0,
+            v_bat: 0.0,
+            data_len: 0,
+            data_crc32: 0,
+            sample_encoding: SampleEncoding::Delta,
+        };
+        let fref = Fref::resolve(&header, fref_base);
+        PageData {
+            channels: vec![ChannelData {
+                records: vec![Record {
+                    timesstamp: 0,
+                    freq: calc_f(info.target, result, fref.value),
+                    result,
+                }],
+                info,
+            }],
+            fref,
+            header,
+            consistant: true,
+            integrity: PageIntegrity {
+                header: ChecksumStatus::Valid,
+                data: ChecksumStatus::Valid,
+            },
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        let p = page(0, 16e6, 10e6, 1);
+        assert_eq!(p.fref.value, 16e6);
+        assert_eq!(p.fref.source, FrefSource::Header);
+
+        let p = page(0, 0.0, 10e6, 1);
+        assert_eq!(p.fref.value, 10e6);
+        assert_eq!(p.fref.source, FrefSource::Default);
+    }
+
+    #[test]
+    fn changes() {
+        let pages = [
+            page(0, 16e6, 0.0, 1),
+            page(1, 16e6, 0.0, 1),
+            page(2, 10e6, 0.0, 1),
+            page(3, f32::NAN, 0.0, 1),
+            page(4, 0.0, 0.0, 1),
+        ];
+        assert_eq!(
+            fref_changes(&pages),
+            vec![
+                FrefChange {
+                    page: 2,
+                    previous: Some(16e6),
+                    actual: Some(10e6),
+                },
+                FrefChange {
+                    page: 3,
+                    previous: Some(10e6),
+                    actual: None,
+                },
+            ]
+        );
+        assert_eq!(
+            fref_changes(&pages)[1].to_string(),
+            "Страница 3: опорная частота не записана, на предыдущей 10000000 Гц"
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let mut chains = Chains {
+            sessions: vec![
+                Chain {
+                    positions: vec![0, 1],
+                    pages: vec![page(0, 16e6, 0.0, 16_000), page(1, 0.0, 0.0, 16_000)],
+                },
+                Chain {
+                    positions: vec![2],
+                    pages: vec![page(0, 16e6, 0.0, 16_000)],
+                },
+            ],
+            ..Default::default()
+        };
+        // страница без опорной частоты распакована с нулевыми частотами
+        assert_eq!(chains.sessions[0].pages[1].channels[0].records[0].freq, 0.0);
+
+        chains.override_fref(&FrefOverrides::default().set(0, 8e6).set(5, 1e6));
+
+        for page in chains.sessions[0].pages.iter() {
+            assert_eq!(page.channels[0].records[0].freq, 500.0);
+            assert_eq!(
+                page.fref,
+                Fref {
+                    value: 8e6,
+                    source: FrefSource::Override
+                }
+            );
+        }
+
+        let second = &chains.sessions[1].pages[0];
+        assert_eq!(second.channels[0].records[0].freq, 1000.0);
+        assert_eq!(second.fref.source, FrefSource::Header);
+    }
+}
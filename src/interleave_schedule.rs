@@ -0,0 +1,132 @@
+use alloc::vec::Vec;
+
+use crate::ChannelInfo;
+
+/// Расписание опроса каналов.
+///
+/// Базовый тик повторяется каждые `base_interval_ms`, канал k опрашивается на
+/// тиках, кратных его `interleave_ratio`. Канал с нулевым делителем не
+/// опрашивается никогда. Итератор выдает только тики, на которых опрашивается
+/// хотя бы один канал, начиная с тика 0, на котором опрашиваются все каналы.
+/// Отсчеты каналов одного тика пишутся в данные в порядке номеров каналов.
+#[derive(Debug, Clone)]
+pub struct InterleaveSchedule {
+    ratios: Vec<u32>,
+    tick: u64,
+}
+
+/// Тик расписания и опрашиваемые на нем каналы
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ScheduledTick {
+    /// номер базового тика от начала страницы
+    pub tick: u64,
+    /// номера опрашиваемых каналов по возрастанию
+    pub channels: Vec<usize>,
+}
+
+impl InterleaveSchedule {
+    /// ratios - делители базового интервала каналов
+    pub fn new(ratios: &[u32]) -> Self {
+        Self {
+            ratios: ratios.to_vec(),
+            tick: 0,
+        }
+    }
+
+    pub fn for_channels(channels: &[ChannelInfo]) -> Self {
+        Self {
+            ratios: channels.iter().map(|c| c.interleave_ratio).collect(),
+            tick: 0,
+        }
+    }
+
+    /// Опрашивается ли канал channel на тике tick
+    pub fn is_due(&self, tick: u64, channel: usize) -> bool {
+        match self.ratios[channel] {
+            0 => false,
+            ratio => tick % ratio as u64 == 0,
+        }
+    }
+
+    /// Ни один канал не опрашивается
+    pub fn is_idle(&self) -> bool {
+        self.ratios.iter().all(|r| *r == 0)
+    }
+}
+
+impl Iterator for InterleaveSchedule {
+    type Item = ScheduledTick;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_idle() {
+            return None;
+        }
+
+        loop {
+            let tick = self.tick;
+            self.tick += 1;
+
+            let channels = (0..self.ratios.len())
+                .filter(|ch| self.is_due(tick, *ch))
+                .collect::<Vec<_>>();
+            if !channels.is_empty() {
+                return Some(ScheduledTick { tick, channels });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::{InterleaveSchedule, ScheduledTick};
+
+    fn tick(tick: u64, channels: Vec<usize>) -> ScheduledTick {
+        ScheduledTick { tick, channels }
+    }
+
+    #[test]
+    fn ratios_3_5() {
+        let ticks = InterleaveSchedule::new(&[3, 5]).take(7).collect::<Vec<_>>();
+
+        assert_eq!(
+            ticks,
+            vec![
+                tick(0, vec![0, 1]),
+                tick(3, vec![0]),
+                tick(5, vec![1]),
+                tick(6, vec![0]),
+                tick(9, vec![0]),
+                tick(10, vec![1]),
+                tick(12, vec![0]),
+            ]
+        );
+
+        let fifteen = InterleaveSchedule::new(&[3, 5])
+            .find(|t| t.tick == 15)
+            .unwrap();
+        assert_eq!(fifteen.channels, vec![0, 1]);
+    }
+
+    #[test]
+    fn ratios_1_0() {
+        let schedule = InterleaveSchedule::new(&[1, 0]);
+        assert!(!schedule.is_due(0, 1));
+        assert!(schedule.is_due(7, 0));
+
+        let ticks = schedule.take(3).collect::<Vec<_>>();
+        assert_eq!(
+            ticks,
+            vec![tick(0, vec![0]), tick(1, vec![0]), tick(2, vec![0])]
+        );
+    }
+
+    #[test]
+    fn idle() {
+        assert!(InterleaveSchedule::new(&[0, 0]).is_idle());
+        assert_eq!(InterleaveSchedule::new(&[0, 0]).next(), None);
+        assert_eq!(InterleaveSchedule::new(&[]).next(), None);
+    }
+}
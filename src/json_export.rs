@@ -0,0 +1,517 @@
+//! Запись в JSON и JSON Lines.
+//!
+//! Страница ([`PageData::write_json()`]):
+//!
+//! ```json
+//! {"prev_block_id":0,"this_block_id":1,"timestamp":1000,"rtc_start_ms":null,
+//!  "f_ref":16000000,"f_ref_used":16000000,"f_ref_source":"header",
+//!  "base_interval_ms":100,"t_cpu":25.5,"v_bat":3.3,"data_len":812,
+//!  "data_crc32":305419896,"sample_encoding":"delta","consistent":true,
+//!  "integrity":{"header":"valid","data":"valid"},
+//!  "channels":[{"name":"FP","target":12000000,"interleave_ratio":1,
+//!                "records":[{"timestamp":1000,"freq":32768.125}]}]}
+//! ```
+//!
+//! `f_ref` - опорная частота из заголовка, `f_ref_used` - по которой
+//! вычислены частоты, `f_ref_source` - откуда она взята: `header`,
+//! `override` или `default` (см. [`crate::FrefSource`]).
+//!
+//! Сеанс ([`SessionSeries::write_json()`]) - такой же объект с полями
+//! `first_block_id`, `start_ms`, `rtc_start_ms`, `base_interval_ms`, `gaps`
+//! и `channels`.
+//!
+//! Если известно время старта записи ([`WallClock`]) - из заголовка или
+//! заданное при записи, - у каждого отсчета есть поле `unix_ms`, время UTC
+//! в мс от 1970-01-01.
+//!
+//! JSON Lines ([`JsonLinesWriter`]) - отсчет на строку:
+//!
+//! ```json
+//! {"session":0,"block":1,"channel":"FP","timestamp":1000,"freq":32768.125}
+//! ```
+//!
+//! Давление и температура ([`write_measurements_json()`]) - массив объектов
+//! `{"timestamp":1000,"pressure":0.98,"temperature":25.1}`.
+//!
+//! Нечисловые значения частоты (деление на нулевой результат) пишутся как `null`.
+
+use std::io::{self, Write};
+
+use crate::{
+    ChannelInfo, ChecksumStatus, FrefSource, Measurement, PageData, Record, SampleEncoding,
+    SeriesGap, SessionSeries, WallClock,
+};
+
+/// Строка JSON в кавычках
+fn string<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    w.write_all(b"\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => w.write_all(b"\\\"")?,
+            '\\' => w.write_all(b"\\\\")?,
+            '\n' => w.write_all(b"\\n")?,
+            '\r' => w.write_all(b"\\r")?,
+            '\t' => w.write_all(b"\\t")?,
+            c if (c as u32) < 0x20 => write!(w, "\\u{:04x}", c as u32)?,
+            c => write!(w, "{}", c)?,
+        }
+    }
+    w.write_all(b"\"")
+}
+
+fn number<W: Write>(w: &mut W, v: f32) -> io::Result<()> {
+    if v.is_finite() {
+        write!(w, "{}", v)
+    } else {
+        w.write_all(b"null")
+    }
+}
+
+fn checksum(status: ChecksumStatus) -> &'static str {
+    match status {
+        ChecksumStatus::Valid => "valid",
+        ChecksumStatus::Invalid => "invalid",
+        ChecksumStatus::Absent => "absent",
+    }
+}
+
+fn encoding(encoding: SampleEncoding) -> &'static str {
+    match encoding {
+        SampleEncoding::Delta => "delta",
+        SampleEncoding::ZigzagVarint => "zigzag_varint",
+    }
+}
+
+fn fref_source(source: FrefSource) -> &'static str {
+    match source {
+        FrefSource::Header => "header",
+        FrefSource::Override => "override",
+        FrefSource::Default => "default",
+    }
+}
+
+fn optional<W: Write>(w: &mut W, v: Option<u64>) -> io::Result<()> {
+    match v {
+        Some(v) => write!(w, "{}", v),
+        None => w.write_all(b"null"),
+    }
+}
+
+/// Поле `unix_ms`, если время старта известно
+fn unix_ms<W: Write>(w: &mut W, clock: Option<&WallClock>, timestamp: u64) -> io::Result<()> {
+    match clock {
+        Some(clock) => write!(w, ",\"unix_ms\":{}", clock.unix_ms(timestamp)),
+        None => Ok(()),
+    }
+}
+
+fn records<W: Write>(w: &mut W, records: &[Record], clock: Option<&WallClock>) -> io::Result<()> {
+    w.write_all(b"[")?;
+    for (i, r) in records.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        write!(w, "{{\"timestamp\":{}", r.timesstamp)?;
+        unix_ms(w, clock, r.timesstamp)?;
+        w.write_all(b",\"freq\":")?;
+        number(w, r.freq)?;
+        w.write_all(b"}")?;
+    }
+    w.write_all(b"]")
+}
+
+/// Массив каналов с их отсчетами
+fn channels<'a, W: Write, I: Iterator<Item = (&'a ChannelInfo, &'a [Record])>>(
+    w: &mut W,
+    channels: I,
+    clock: Option<&WallClock>,
+) -> io::Result<()> {
+    w.write_all(b"[")?;
+    for (i, (info, r)) in channels.enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        w.write_all(b"{\"name\":")?;
+        string(w, &info.name)?;
+        write!(
+            w,
+            ",\"target\":{},\"interleave_ratio\":{},\"records\":",
+            info.target, info.interleave_ratio
+        )?;
+        records(w, r, clock)?;
+        w.write_all(b"}")?;
+    }
+    w.write_all(b"]")
+}
+
+fn gaps<W: Write>(w: &mut W, gaps: &[SeriesGap]) -> io::Result<()> {
+    w.write_all(b"[")?;
+    for (i, gap) in gaps.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        write!(w, "{{\"from_ms\":{},\"to_ms\":{}}}", gap.from_ms, gap.to_ms)?;
+    }
+    w.write_all(b"]")
+}
+
+impl PageData {
+    /// Записать страницу одним объектом JSON
+    pub fn write_json<W: Write>(&self, w: W) -> io::Result<()> {
+        self.write_json_with(w, None)
+    }
+
+    /// Записать страницу одним объектом JSON, clock - время старта записи,
+    /// None - из заголовка страницы
+    pub fn write_json_with<W: Write>(&self, mut w: W, clock: Option<&WallClock>) -> io::Result<()> {
+        let h = &self.header;
+        let header_clock = WallClock::from_header(h);
+        write!(
+            w,
+            "{{\"prev_block_id\":{},\"this_block_id\":{},\"timestamp\":{},\"rtc_start_ms\":",
+            h.prev_block_id, h.this_block_id, h.timestamp
+        )?;
+        optional(&mut w, h.rtc_start_ms)?;
+        w.write_all(b",\"f_ref\":")?;
+        number(&mut w, h.f_ref)?;
+        w.write_all(b",\"f_ref_used\":")?;
+        number(&mut w, self.fref.value)?;
+        write!(w, ",\"f_ref_source\":\"{}\"", fref_source(self.fref.source))?;
+        write!(w, ",\"base_interval_ms\":{},\"t_cpu\":", h.base_interval_ms)?;
+        number(&mut w, h.t_cpu)?;
+        w.write_all(b",\"v_bat\":")?;
+        number(&mut w, h.v_bat)?;
+        write!(
+            w,
+            ",\"data_len\":{},\"data_crc32\":{},\"sample_encoding\":\"{}\",\"consistent\":{}",
+            h.data_len,
+            h.data_crc32,
+            encoding(h.sample_encoding),
+            self.consistant
+        )?;
+        write!(
+            w,
+            ",\"integrity\":{{\"header\":\"{}\",\"data\":\"{}\"}},\"channels\":",
+            checksum(self.integrity.header),
+            checksum(self.integrity.data)
+        )?;
+        channels(
+            &mut w,
+            self.channels
+                .iter()
+                .map(|c| (&c.info, c.records.as_slice())),
+            clock.or(header_clock.as_ref()),
+        )?;
+        w.write_all(b"}")
+    }
+}
+
+impl SessionSeries {
+    /// Записать весь ряд одним объектом JSON
+    pub fn write_json<W: Write>(&self, w: W) -> io::Result<()> {
+        self.write_json_with(w, None)
+    }
+
+    /// Записать весь ряд одним объектом JSON, clock - время старта записи,
+    /// None - из заголовков страниц
+    pub fn write_json_with<W: Write>(&self, mut w: W, clock: Option<&WallClock>) -> io::Result<()> {
+        let series_clock = self.wall_clock();
+        write!(
+            w,
+            "{{\"first_block_id\":{},\"start_ms\":{},\"rtc_start_ms\":",
+            self.first_block_id, self.start_ms
+        )?;
+        optional(&mut w, self.rtc_start_ms)?;
+        write!(
+            w,
+            ",\"base_interval_ms\":{},\"gaps\":",
+            self.base_interval_ms
+        )?;
+        gaps(&mut w, &self.gaps)?;
+        w.write_all(b",\"channels\":")?;
+        channels(
+            &mut w,
+            self.channels
+                .iter()
+                .map(|c| (&c.info, c.records.as_slice())),
+            clock.or(series_clock.as_ref()),
+        )?;
+        w.write_all(b"}")
+    }
+}
+
+/// Записать давление и температуру массивом JSON:
+/// `[{"timestamp":1000,"pressure":0.98,"temperature":25.1}]`
+pub fn write_measurements_json<W: Write>(measurements: &[Measurement], mut w: W) -> io::Result<()> {
+    w.write_all(b"[")?;
+    for (i, m) in measurements.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        write!(w, "{{\"timestamp\":{},\"pressure\":", m.timestamp)?;
+        number(&mut w, m.pressure)?;
+        w.write_all(b",\"temperature\":")?;
+        number(&mut w, m.temperature)?;
+        w.write_all(b"}")?;
+    }
+    w.write_all(b"]")
+}
+
+/// Запись отсчетов в JSON Lines по одной странице, без накопления страниц.
+///
+/// Страницы подаются по цепочкам в их порядке, например по
+/// [`crate::Chain::positions`]: перед первой страницей цепочки номер её
+/// сеанса задает [`JsonLinesWriter::start_chain()`]. Порядок страниц во флеше
+/// для этого не годится: после перехода с конца флеши на начало сеанс
+/// начинается не с первой страницы образа. Как и в [`SessionSeries`],
+/// отсчеты канала, не продолжающие его ряд по времени, пропускаются.
+/// Поле `unix_ms` пишется, если время старта задано [`JsonLinesWriter::with_clock()`]
+/// или записано в заголовке страницы.
+pub struct JsonLinesWriter<W: Write> {
+    w: W,
+    /// время старта, заданное при записи
+    clock: Option<WallClock>,
+    /// номер сеанса текущей цепочки, None - цепочка без начала
+    session: Option<usize>,
+    /// время последнего записанного отсчета каналов текущего сеанса
+    last: Vec<(String, Option<u64>)>,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(w: W) -> Self {
+        Self {
+            w,
+            clock: None,
+            session: None,
+            last: Vec::new(),
+        }
+    }
+
+    /// Время старта записи для всех страниц вместо времени из заголовков
+    pub fn with_clock(mut self, clock: WallClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Начать цепочку: session - номер сеанса в [`crate::Chains::sessions`],
+    /// None - цепочка из [`crate::Chains::orphans`]
+    pub fn start_chain(&mut self, session: Option<usize>) {
+        self.session = session;
+        self.last.clear();
+    }
+
+    /// Записать отсчеты следующей страницы текущей цепочки
+    pub fn write_page(&mut self, page: &PageData) -> io::Result<()> {
+        let clock = self.clock.or_else(|| WallClock::from_header(&page.header));
+        for channel in page.channels.iter() {
+            let i = match self.last.iter().position(|(n, _)| *n == channel.info.name) {
+                Some(i) => i,
+                None => {
+                    self.last.push((channel.info.name.clone(), None));
+                    self.last.len() - 1
+                }
+            };
+
+            for r in channel.records.iter() {
+                if matches!(self.last[i].1, Some(last) if r.timesstamp <= last) {
+                    continue;
+                }
+                self.last[i].1 = Some(r.timesstamp);
+                Self::line(&mut self.w, self.session, clock, page, &channel.info, r)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn line(
+        w: &mut W,
+        session: Option<usize>,
+        clock: Option<WallClock>,
+        page: &PageData,
+        info: &ChannelInfo,
+        r: &Record,
+    ) -> io::Result<()> {
+        w.write_all(b"{\"session\":")?;
+        optional(w, session.map(|s| s as u64))?;
+        write!(w, ",\"block\":{},\"channel\":", page.header.this_block_id)?;
+        string(w, &info.name)?;
+        write!(w, ",\"timestamp\":{}", r.timesstamp)?;
+        unix_ms(w, clock.as_ref(), r.timesstamp)?;
+        w.write_all(b",\"freq\":")?;
+        number(w, r.freq)?;
+        w.write_all(b"}\n")
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{string, JsonLinesWriter};
+    use crate::{
+        build_chains, ChannelData, ChannelInfo, ChecksumStatus, DataPacketHeader, Fref, FrefSource,
+        PageData, PageIntegrity, Record, SampleEncoding, WallClock,
+    };
+
+    fn page(prev: u32, this: u32, timestamp: u64, freqs: &[f32]) -> PageData {
+        let info = ChannelInfo::new("FP", 12_000_000, 1);
+        PageData {
+            header: DataPacketHeader {
+                prev_block_id: prev,
+                this_block_id: this,
+                timestamp,
+                rtc_start_ms: None,
+                f_ref: 16_000_000.0,
+                base_interval_ms: 100,
+                channels: vec![info.clone()],
+                t_cpu: 25.5,
+                v_bat: 3.25,
+                data_len: 10,
+                data_crc32: 7,
+                sample_encoding: SampleEncoding::Delta,
+            },
+            fref: Fref {
+                value: 16_000_000.0,
+                source: FrefSource::Header,
+            },
+            consistant: true,
+            integrity: PageIntegrity {
+                header: ChecksumStatus::Valid,
+                data: ChecksumStatus::Valid,
+            },
+            channels: vec![ChannelData {
+                info,
+                records: freqs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, freq)| Record {
+                        timesstamp: timestamp + i as u64 * 100,
+                        freq: *freq,
+                        ..Default::default()
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    #[test]
+    fn escape() {
+        let mut buf = Vec::new();
+        string(&mut buf, "a\"b\\c\n\u{1}д").unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), r#""a\"b\\c\n\u0001д""#);
+    }
+
+    #[test]
+    fn page_json() {
+        let mut buf = Vec::new();
+        page(0, 0, 1000, &[32768.125, f32::INFINITY])
+            .write_json(&mut buf)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\"prev_block_id\":0,\"this_block_id\":0,\"timestamp\":1000,\"rtc_start_ms\":null,\
+             \"f_ref\":16000000,\"f_ref_used\":16000000,\"f_ref_source\":\"header\",\
+             \"base_interval_ms\":100,\"t_cpu\":25.5,\"v_bat\":3.25,\"data_len\":10,\
+             \"data_crc32\":7,\"sample_encoding\":\"delta\",\"consistent\":true,\
+             \"integrity\":{\"header\":\"valid\",\"data\":\"valid\"},\
+             \"channels\":[{\"name\":\"FP\",\"target\":12000000,\"interleave_ratio\":1,\
+             \"records\":[{\"timestamp\":1000,\"freq\":32768.125},\
+             {\"timestamp\":1100,\"freq\":null}]}]}"
+        );
+    }
+
+    #[test]
+    fn unix_time() {
+        let mut rtc = page(0, 0, 0, &[1.0]);
+        rtc.header.rtc_start_ms = Some(1_614_834_367_089);
+
+        let mut w = JsonLinesWriter::new(Vec::new());
+        w.start_chain(Some(0));
+        w.write_page(&rtc).unwrap();
+        w.start_chain(Some(1));
+        w.write_page(&page(0, 0, 0, &[2.0])).unwrap();
+        let mut w = JsonLinesWriter::new(w.into_inner()).with_clock(WallClock::new(1000));
+        w.start_chain(Some(0));
+        w.write_page(&page(0, 0, 0, &[3.0])).unwrap();
+
+        let out = String::from_utf8(w.into_inner()).unwrap();
+        assert_eq!(
+            out.lines().collect::<Vec<_>>(),
+            vec![
+                "{\"session\":0,\"block\":0,\"channel\":\"FP\",\"timestamp\":0,\"unix_ms\":1614834367089,\"freq\":1}",
+                "{\"session\":1,\"block\":0,\"channel\":\"FP\",\"timestamp\":0,\"freq\":2}",
+                "{\"session\":0,\"block\":0,\"channel\":\"FP\",\"timestamp\":0,\"unix_ms\":1000,\"freq\":3}",
+            ]
+        );
+
+        let mut buf = Vec::new();
+        rtc.write_json_with(&mut buf, Some(&WallClock::new(0).with_sync(100, 200)))
+            .unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.contains("\"rtc_start_ms\":1614834367089,"));
+        assert!(text.contains("\"records\":[{\"timestamp\":0,\"unix_ms\":0,\"freq\":1}]"));
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut w = JsonLinesWriter::new(Vec::new());
+        // продолжение без начала, сеанс, повтор тика на стыке страниц, новый сеанс
+        w.start_chain(None);
+        w.write_page(&page(7, 8, 5000, &[1.0])).unwrap();
+        w.start_chain(Some(0));
+        w.write_page(&page(0, 0, 0, &[2.0, 3.0])).unwrap();
+        w.write_page(&page(0, 1, 100, &[3.0, 4.0])).unwrap();
+        w.start_chain(Some(1));
+        w.write_page(&page(0, 0, 0, &[5.0])).unwrap();
+
+        let out = String::from_utf8(w.into_inner()).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                "{\"session\":null,\"block\":8,\"channel\":\"FP\",\"timestamp\":5000,\"freq\":1}",
+                "{\"session\":0,\"block\":0,\"channel\":\"FP\",\"timestamp\":0,\"freq\":2}",
+                "{\"session\":0,\"block\":0,\"channel\":\"FP\",\"timestamp\":100,\"freq\":3}",
+                "{\"session\":0,\"block\":1,\"channel\":\"FP\",\"timestamp\":200,\"freq\":4}",
+                "{\"session\":1,\"block\":0,\"channel\":\"FP\",\"timestamp\":0,\"freq\":5}",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines_wrapped_flash() {
+        // запись дошла до конца флеши и продолжилась с её начала
+        let dump = vec![
+            page(1, 2, 200, &[3.0]),
+            page(2, 3, 300, &[4.0]),
+            page(0, 0, 0, &[1.0]),
+            page(0, 1, 100, &[2.0]),
+        ];
+        let chains = build_chains(dump.into_iter().map(Ok).collect());
+        assert_eq!(chains.sessions[0].positions, vec![2, 3, 0, 1]);
+
+        let mut w = JsonLinesWriter::new(Vec::new());
+        for (session, chain) in chains.sessions.iter().enumerate() {
+            w.start_chain(Some(session));
+            for page in chain.pages.iter() {
+                w.write_page(page).unwrap();
+            }
+        }
+
+        let out = String::from_utf8(w.into_inner()).unwrap();
+        assert_eq!(
+            out.lines().collect::<Vec<_>>(),
+            (0..4)
+                .map(|i| format!(
+                    "{{\"session\":0,\"block\":{},\"channel\":\"FP\",\"timestamp\":{},\"freq\":{}}}",
+                    i,
+                    i * 100,
+                    i + 1
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -2,56 +2,103 @@
 
 extern crate alloc;
 
-#[derive(PartialEq, Debug, Clone)]
-pub struct DataPacketHeader {
-    /// номер этого блока
-    pub prev_block_id: u32,
-    /// номер предыдущего блока в цепочке
-    pub this_block_id: u32,
-
-    /// таймштамп, время от старта записи
-    pub timestamp: u64,
-    /// опорная частота, она могла меняться между цепочками
-    pub f_ref: f32,
-
-    /// таргеты
-    pub targets: [u32; 2],
-
-    /// базовый интервал записи, мс
-    pub base_interval_ms: u32,
-    /// делители базового интервала
-    pub interleave_ratio: [u32; 2],
-
-    /// температура процессора
-    pub t_cpu: f32,
-    /// заряд батареи
-    pub v_bat: f32,
-
-    /// Фактическое количество значащих байт в блоке, не считая еиспользованные с конц байты
-    pub data_len: u32,
-    /// CRC32 (zlib)
-    pub data_crc32: u32,
-}
-
-impl DataPacketHeader {
-    pub fn is_initial(&self) -> bool {
-        self.prev_block_id == 0 && self.this_block_id == 0
-    }
-}
+mod error;
+pub use error::Error;
+
+mod data_packet_header;
+pub use data_packet_header::{
+    ChannelInfo, DataPacketHeader, FormatVersion, PageHeader, LEGACY_CHANNEL_NAMES, PAGE_MAGIC,
+};
 
 mod data_block_packer;
-pub use data_block_packer::{DataBlockPacker, PushResult};
+pub use data_block_packer::{DataBlockPacker, DataBlockPackerBuilder, PushResult};
 
 mod data_block_unpacker;
-pub use data_block_unpacker::DataBlockUnPacker;
+pub use data_block_unpacker::{
+    ChecksumStatus, DataBlockUnPacker, PageIntegrity, PageKind, PlainValue,
+};
+
+mod interleave_schedule;
+pub use interleave_schedule::{InterleaveSchedule, ScheduledTick};
+
+mod sample_codec;
+pub use sample_codec::{SampleDecoder, SampleEncoder, SampleEncoding};
+
+mod chain_recorder;
+pub use chain_recorder::{ChainRecorder, PageSink};
 
 #[cfg(feature = "unpacker")]
 mod data_unpacker;
 #[cfg(feature = "unpacker")]
 pub use data_unpacker::*;
 
+#[cfg(feature = "unpacker")]
+mod fref;
+#[cfg(feature = "unpacker")]
+pub use fref::{fref_changes, Fref, FrefChange, FrefOverrides, FrefSource};
+
+#[cfg(feature = "unpacker")]
+mod wall_clock;
+#[cfg(feature = "unpacker")]
+pub use wall_clock::WallClock;
+
+#[cfg(feature = "unpacker")]
+mod csv_export;
+#[cfg(feature = "unpacker")]
+pub use csv_export::{CsvOptions, HeaderLanguage, TimestampStyle};
+
+#[cfg(feature = "unpacker")]
+mod calibration;
+#[cfg(feature = "unpacker")]
+pub use calibration::{write_measurements_csv, Calibration, CalibrationError, Measurement};
+
+#[cfg(feature = "unpacker")]
+mod calibration_fit;
+#[cfg(feature = "unpacker")]
+pub use calibration_fit::{
+    fit_calibration, CalibrationFit, CalibrationPoint, FitDegrees, FitError, Residuals,
+};
+
+#[cfg(feature = "unpacker")]
+mod json_export;
+#[cfg(feature = "unpacker")]
+pub use json_export::{write_measurements_json, JsonLinesWriter};
+
+#[cfg(feature = "unpacker")]
+mod chains;
+#[cfg(feature = "unpacker")]
+pub use chains::{build_chains, Chain, Chains, Fork, Merge, MissingLink};
+
+#[cfg(feature = "unpacker")]
+mod continuity;
+#[cfg(feature = "unpacker")]
+pub use continuity::{analyze_continuity, ContinuityEvent};
+
+#[cfg(feature = "unpacker")]
+mod session_series;
+#[cfg(feature = "unpacker")]
+pub use session_series::{ChannelSeries, SeriesGap, SessionSeries};
+
+#[cfg(feature = "unpacker")]
+mod page_reader;
+#[cfg(feature = "unpacker")]
+pub use page_reader::{PageReader, ReadError};
+
+#[cfg(feature = "unpacker")]
+mod recovery;
+#[cfg(feature = "unpacker")]
+pub use recovery::{recover_page, RecoveredPage, RecoveryLimits, RecoveryStop, StopReason};
+
+#[cfg(feature = "unpacker")]
+mod telemetry;
+#[cfg(feature = "unpacker")]
+pub use telemetry::{
+    AlertKind, BatteryForecast, Telemetry, TelemetryAlert, TelemetryPoint, TelemetryThresholds,
+};
+
 // https://github.com/sdleffler/empty-box-rs
 mod empty_box;
 pub use empty_box::EmptyBox;
 
-pub(crate) mod add_signed;
\ No newline at end of file
+pub(crate) mod add_signed;
+pub(crate) mod wire;
@@ -0,0 +1,167 @@
+//! Потоковое чтение страниц из любого [`Read`]: файла, канала, порта.
+//!
+//! В памяти одновременно находится не больше `parallel` страниц: они читаются
+//! пачкой, распаковываются параллельно и выдаются по одной в порядке чтения.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read};
+
+use crate::data_unpacker::unpack_page;
+use crate::{Error, PageData};
+
+/// Ошибка потокового чтения
+#[derive(Debug)]
+pub enum ReadError {
+    /// ошибка чтения источника, чтение прекращается
+    Io(io::Error),
+    /// страницу не удалось разобрать, чтение продолжается
+    Page(Error),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "read error: {}", e),
+            ReadError::Page(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(e) => Some(e),
+            ReadError::Page(e) => Some(e),
+        }
+    }
+}
+
+impl From<Error> for ReadError {
+    fn from(e: Error) -> Self {
+        ReadError::Page(e)
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+/// Итератор по страницам потока, аналог [`crate::unpack_pages()`].
+///
+/// Страницы, которые не удалось разобрать, дают [`ReadError::Page`] и не
+/// прерывают чтение. Неполная последняя страница тоже разбирается, её
+/// размер - [`PageReader::truncated_tail()`].
+/// После [`ReadError::Io`] итератор заканчивается.
+pub struct PageReader<R: Read> {
+    reader: R,
+    page_size: usize,
+    fref_base: f32,
+    ignore_inconsistant: bool,
+    parallel: usize,
+    ready: VecDeque<Result<PageData, ReadError>>,
+    eof: bool,
+    /// размер неполной последней страницы
+    truncated: Option<usize>,
+}
+
+impl<R: Read> PageReader<R> {
+    /// reader - источник данных
+    /// page_size - размер страницы
+    /// fref - опорная частота из настроек
+    /// ignore_inconsistant - игнорировать ошибки и продлолжать
+    ///
+    /// Паникует, если page_size = 0
+    pub fn new(reader: R, page_size: usize, fref_base: f32, ignore_inconsistant: bool) -> Self {
+        assert!(page_size > 0, "Page size must not be zero");
+        Self {
+            reader,
+            page_size,
+            fref_base,
+            ignore_inconsistant,
+            parallel: 1,
+            ready: VecDeque::new(),
+            eof: false,
+            truncated: None,
+        }
+    }
+
+    /// Распаковывать параллельно до pages страниц, 1 - последовательно
+    pub fn set_parallel(mut self, pages: usize) -> Self {
+        self.parallel = pages.max(1);
+        self
+    }
+
+    /// Размер неполной последней страницы потока, если она уже прочитана,
+    /// None - страницы целые или поток еще не дочитан
+    pub fn truncated_tail(&self) -> Option<usize> {
+        self.truncated
+    }
+
+    /// Прочитать страницу целиком, меньше page_size байт - конец потока
+    fn read_page(&mut self) -> io::Result<Vec<u8>> {
+        let mut page = vec![0u8; self.page_size];
+        let mut len = 0;
+        while len < page.len() {
+            match self.reader.read(&mut page[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        page.truncate(len);
+        Ok(page)
+    }
+
+    /// Прочитать и распаковать следующую пачку страниц
+    fn fill(&mut self) {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        let mut pages = Vec::with_capacity(self.parallel);
+        let mut io_error = None;
+        while pages.len() < self.parallel && !self.eof {
+            match self.read_page() {
+                Ok(page) => {
+                    if page.len() < self.page_size {
+                        self.eof = true;
+                        if page.is_empty() {
+                            break;
+                        }
+                        self.truncated = Some(page.len());
+                    }
+                    pages.push(page);
+                }
+                Err(e) => {
+                    self.eof = true;
+                    io_error = Some(e);
+                }
+            }
+        }
+
+        let (fref_base, ignore_inconsistant) = (self.fref_base, self.ignore_inconsistant);
+        let unpack =
+            |page: Vec<u8>| unpack_page(&page, fref_base, ignore_inconsistant).map_err(Into::into);
+        if pages.len() > 1 {
+            let decoded: Vec<_> = pages.into_par_iter().map(unpack).collect();
+            self.ready.extend(decoded);
+        } else {
+            self.ready.extend(pages.into_iter().map(unpack));
+        }
+
+        self.ready.extend(io_error.map(|e| Err(e.into())));
+    }
+}
+
+impl<R: Read> Iterator for PageReader<R> {
+    type Item = Result<PageData, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ready.is_empty() && !self.eof {
+            self.fill();
+        }
+        self.ready.pop_front()
+    }
+}
@@ -0,0 +1,192 @@
+//! Восстановление отсчетов из поврежденных страниц.
+//!
+//! Сжатый поток распаковывается с начала, отсчеты читаются, пока данные
+//! остаются правдоподобными. Распаковщик heatshrink не сообщает о неверных
+//! обратных ссылках, поэтому повреждение обнаруживается по распакованным
+//! данным: оборванный или неверно закодированный отсчет, выход результата за
+//! пределы u32, нулевой результат, скачок результата канала или слишком
+//! длинные для `data_len` распакованные данные. Все отсчеты до первого такого
+//! места возвращаются вместе с описанием места остановки.
+
+use crate::data_unpacker::calc_f;
+use crate::{
+    DataBlockUnPacker, Error, InterleaveSchedule, PageData, PageKind, Record, SampleDecoder,
+    SampleEncoding,
+};
+
+/// Пределы правдоподобия восстанавливаемых данных
+#[derive(Debug, Clone, Copy)]
+pub struct RecoveryLimits {
+    /// наибольшее относительное изменение результата канала между соседними отсчетами
+    pub max_step: f32,
+    /// наибольшее отношение длины распакованных данных к `data_len`
+    pub max_expansion: usize,
+}
+
+impl Default for RecoveryLimits {
+    fn default() -> Self {
+        Self {
+            max_step: 0.5,
+            max_expansion: 16,
+        }
+    }
+}
+
+/// Почему остановилось восстановление
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum StopReason {
+    /// отсчет оборван или закодирован неверно, либо результат вышел за пределы u32
+    Malformed(Error),
+    /// нулевой результат или скачок результата больше [`RecoveryLimits::max_step`]
+    Implausible {
+        /// номер канала
+        channel: usize,
+        /// номер отсчета в канале
+        sample: usize,
+    },
+    /// распакованные данные длиннее, чем может дать `data_len`
+    TooLong,
+}
+
+/// Место остановки восстановления
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct RecoveryStop {
+    /// смещение первого непрочитанного байта распакованных данных
+    pub offset: usize,
+    /// номер базового тика от начала страницы
+    pub tick: u64,
+    pub reason: StopReason,
+}
+
+/// Результат восстановления страницы
+pub struct RecoveredPage {
+    /// заголовок и отсчеты до места остановки
+    pub page: PageData,
+    /// где остановилось восстановление, None - данные прочитаны до конца
+    pub stop: Option<RecoveryStop>,
+}
+
+impl RecoveredPage {
+    /// Данные прочитаны до конца без признаков повреждения
+    pub fn is_complete(&self) -> bool {
+        self.stop.is_none()
+    }
+}
+
+/// Восстановить отсчеты страницы, в том числе поврежденной.
+/// Заголовок должен разбираться, его CRC32 не проверяется.
+/// fref - опорная частота из настроек
+pub fn recover_page(
+    page: &[u8],
+    fref_base: f32,
+    limits: &RecoveryLimits,
+) -> Result<RecoveredPage, Error> {
+    if let Some(e) = PageKind::empty(page).and_then(PageKind::error) {
+        return Err(e);
+    }
+
+    let unpacker = DataBlockUnPacker::new(page);
+    let mut result = PageData::without_records(unpacker.hader()?, unpacker.integrity(), fref_base);
+    let fref = result.fref.value;
+
+    let limit = (result.header.data_len as usize).saturating_mul(limits.max_expansion);
+    let (mut data, too_long) = unpacker.unpack_data_lossy(limit)?;
+    let mut end_reason = if too_long {
+        Some(StopReason::TooLong)
+    } else {
+        None
+    };
+
+    if result.header.sample_encoding == SampleEncoding::Delta && data.len() % 4 != 0 {
+        let len = data.len();
+        data.truncate(len - len % 4);
+        end_reason = end_reason.or(Some(StopReason::Malformed(Error::BadAlignment {
+            len,
+            align: 4,
+        })));
+    }
+
+    let channels = result.channels.len();
+    let mut decoder = SampleDecoder::new(&data, result.header.sample_encoding, channels)?;
+    let mut prevs = vec![None; channels];
+    let mut stop = None;
+    let mut last_tick = 0;
+
+    'decode: for tick in InterleaveSchedule::for_channels(&result.header.channels) {
+        last_tick = tick.tick;
+        for ch in tick.channels {
+            let offset = data.len() - decoder.remaining();
+            let sample = result.channels[ch].records.len();
+            let reason = match decoder.next_result(ch) {
+                Ok(None) => break 'decode,
+                Ok(Some(v)) if plausible(prevs[ch], v, limits) => {
+                    prevs[ch] = Some(v);
+                    let channel = &mut result.channels[ch];
+                    channel.records.push(Record {
+                        freq: calc_f(channel.info.target, v, fref),
+                        timesstamp: result.header.timestamp
+                            + tick.tick * result.header.base_interval_ms as u64,
+                        result: v,
+                    });
+                    continue;
+                }
+                Ok(Some(_)) => StopReason::Implausible {
+                    channel: ch,
+                    sample,
+                },
+                Err(e) => StopReason::Malformed(e),
+            };
+
+            stop = Some(RecoveryStop {
+                offset,
+                tick: tick.tick,
+                reason,
+            });
+            break 'decode;
+        }
+    }
+
+    if stop.is_none() {
+        let offset = data.len() - decoder.remaining();
+        stop = match end_reason {
+            Some(reason) => Some(RecoveryStop {
+                offset,
+                tick: last_tick,
+                reason,
+            }),
+            // у всех каналов нулевой делитель, данные не прочитать
+            None if offset < data.len() => Some(RecoveryStop {
+                offset,
+                tick: 0,
+                reason: StopReason::Malformed(Error::ZeroInterleave),
+            }),
+            None => None,
+        };
+    }
+
+    Ok(RecoveredPage { page: result, stop })
+}
+
+fn plausible(prev: Option<u32>, v: u32, limits: &RecoveryLimits) -> bool {
+    match prev {
+        _ if v == 0 => false,
+        Some(prev) => (v as f32 - prev as f32).abs() <= prev as f32 * limits.max_step,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plausible, RecoveryLimits};
+
+    #[test]
+    fn plausibility() {
+        let limits = RecoveryLimits::default();
+        assert!(plausible(None, 12_000_000, &limits));
+        assert!(!plausible(None, 0, &limits));
+        assert!(plausible(Some(12_000_000), 12_000_100, &limits));
+        assert!(plausible(Some(12_000_000), 6_000_000, &limits));
+        assert!(!plausible(Some(12_000_000), 5_000_000, &limits));
+        assert!(!plausible(Some(12_000_000), 30_000_000, &limits));
+    }
+}
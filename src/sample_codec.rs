@@ -0,0 +1,390 @@
+//! Кодирование результатов счетчиков перед сжатием.
+//!
+//! В страницу пишутся не сами результаты, а разности с предыдущим результатом
+//! того же канала. Первый отсчет канала на странице от предыдущих страниц не
+//! зависит, так что каждая страница декодируется независимо от остальных.
+//!
+//! [`SampleEncoding::Delta`] - первый отсчет канала как `u32`, следующие -
+//! разность как `i32` little-endian, 4 байта на отсчет, так пишут страницы
+//! версии 1 и код, вызывающий [`DataBlockPacker::push_val`] напрямую.
+//! Разность больше `i32` так не записать.
+//!
+//! [`SampleEncoding::ZigzagVarint`] - разность (для первого отсчета - с нулем)
+//! переводится в беззнаковое число zigzag-преобразованием и записывается LEB128
+//! по 7 бит на байт: разности в пределах ±63 занимают 1 байт, ±8191 - 2 байта,
+//! любая разность двух `u32` - не больше 5 байт.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::add_signed::AddSigned;
+use crate::{DataBlockPacker, Error, PushResult};
+
+/// Способ записи разностей отсчетов в данные страницы
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum SampleEncoding {
+    /// i32 little-endian
+    Delta = 0,
+    /// zigzag + LEB128
+    ZigzagVarint = 1,
+}
+
+impl SampleEncoding {
+    /// Максимальный размер одного отсчета, байт
+    pub const MAX_SAMPLE_SIZE: usize = 5;
+
+    pub fn from_u8(v: u8) -> Result<Self, Error> {
+        match v {
+            0 => Ok(SampleEncoding::Delta),
+            1 => Ok(SampleEncoding::ZigzagVarint),
+            v => Err(Error::UnsupportedSampleEncoding(v)),
+        }
+    }
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn unzigzag(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+/// Что записать для результата канала: разность с предыдущим результатом prev
+/// или, для первого отсчета канала на странице (prev = None), в Delta сам
+/// результат, в ZigzagVarint - разность с нулем. None - разность не помещается в i32
+fn sample(encoding: SampleEncoding, prev: Option<u32>, result: u32) -> Option<i64> {
+    let diff = result as i64 - prev.unwrap_or(0) as i64;
+    match (encoding, prev) {
+        (SampleEncoding::Delta, Some(_)) if diff < i32::MIN as i64 || diff > i32::MAX as i64 => {
+            None
+        }
+        _ => Some(diff),
+    }
+}
+
+/// Закодировать отсчет, вернуть буфер и количество занятых в нем байт.
+/// В Delta пишутся младшие 32 бита: разность как i32 или результат как u32
+fn encode(encoding: SampleEncoding, v: i64) -> ([u8; SampleEncoding::MAX_SAMPLE_SIZE], usize) {
+    let mut buf = [0u8; SampleEncoding::MAX_SAMPLE_SIZE];
+    match encoding {
+        SampleEncoding::Delta => {
+            buf[..4].copy_from_slice(&(v as u32).to_le_bytes());
+            (buf, 4)
+        }
+        SampleEncoding::ZigzagVarint => {
+            let mut v = zigzag(v);
+            let mut len = 0;
+            loop {
+                let byte = (v & 0x7F) as u8;
+                v >>= 7;
+                if v == 0 {
+                    buf[len] = byte;
+                    return (buf, len + 1);
+                }
+                buf[len] = byte | 0x80;
+                len += 1;
+            }
+        }
+    }
+}
+
+/// Разностный кодировщик результатов поверх упаковщика страницы.
+/// Способ кодирования берется из заголовка упаковщика, его задает
+/// `set_sample_encoding()` при построении упаковщика
+pub struct SampleEncoder {
+    packer: DataBlockPacker,
+    prevs: Vec<Option<u32>>,
+}
+
+impl SampleEncoder {
+    pub fn new(packer: DataBlockPacker) -> Self {
+        Self {
+            prevs: vec![None; packer.header.channels.len()],
+            packer,
+        }
+    }
+
+    /// Записать результат счетчика канала channel.
+    /// Отсчет, на котором страница заполнилась (PushResult::Full), в нее попадает.
+    /// Любой результат записывается первым отсчетом канала на странице, но в
+    /// [`SampleEncoding::Delta`] разность с предыдущим результатом канала может
+    /// не поместиться в i32, тогда отсчет не записывается: PushResult::OutOfRange
+    pub fn push(&mut self, channel: usize, result: u32) -> PushResult {
+        let encoding = self.packer.header.sample_encoding;
+        let prev = &mut self.prevs[channel];
+        let v = match sample(encoding, *prev, result) {
+            Some(v) => v,
+            None => return PushResult::OutOfRange,
+        };
+        let (buf, len) = encode(encoding, v);
+
+        let res = self.packer.push_bytes(&buf[..len]);
+        if let PushResult::Success | PushResult::Full = res {
+            *prev = Some(result);
+        }
+        res
+    }
+
+    /// Записать результаты нескольких каналов, например одного тика, одним куском:
+    /// samples - пары (канал, результат), каждый канал не больше одного раза.
+    /// Либо в страницу попадают все отсчеты, либо ни одного: если хотя бы один
+    /// не записать, PushResult::OutOfRange, если не поместились - PushResult::Overflow
+    pub fn push_all(&mut self, samples: &[(usize, u32)]) -> PushResult {
+        let encoding = self.packer.header.sample_encoding;
+        let mut bytes = Vec::with_capacity(samples.len() * SampleEncoding::MAX_SAMPLE_SIZE);
+        for (channel, result) in samples.iter() {
+            match sample(encoding, self.prevs[*channel], *result) {
+                Some(v) => {
+                    let (buf, len) = encode(encoding, v);
+                    bytes.extend_from_slice(&buf[..len]);
+                }
+                None => return PushResult::OutOfRange,
+            }
+        }
+
+        let res = self.packer.push_bytes(&bytes);
+        if let PushResult::Success | PushResult::Full = res {
+            for (channel, result) in samples.iter() {
+                self.prevs[*channel] = Some(*result);
+            }
+        }
+        res
+    }
+
+    /// Можно ли записать результат канала channel следующим отсчетом
+    pub fn fits(&self, channel: usize, result: u32) -> bool {
+        sample(
+            self.packer.header.sample_encoding,
+            self.prevs[channel],
+            result,
+        )
+        .is_some()
+    }
+
+    pub fn packer(&self) -> &DataBlockPacker {
+        &self.packer
+    }
+
+    pub fn packer_mut(&mut self) -> &mut DataBlockPacker {
+        &mut self.packer
+    }
+
+    pub fn into_packer(self) -> DataBlockPacker {
+        self.packer
+    }
+}
+
+/// Декодер распакованных данных страницы, парный [`SampleEncoder`]
+pub struct SampleDecoder<'a> {
+    data: &'a [u8],
+    encoding: SampleEncoding,
+    prevs: Vec<Option<u32>>,
+    counts: Vec<usize>,
+}
+
+impl<'a> SampleDecoder<'a> {
+    /// data - распакованные данные страницы
+    /// channels - количество каналов
+    pub fn new(data: &'a [u8], encoding: SampleEncoding, channels: usize) -> Result<Self, Error> {
+        if encoding == SampleEncoding::Delta && data.len() % 4 != 0 {
+            return Err(Error::BadAlignment {
+                len: data.len(),
+                align: 4,
+            });
+        }
+
+        Ok(Self {
+            data,
+            encoding,
+            prevs: vec![None; channels],
+            counts: vec![0; channels],
+        })
+    }
+
+    /// Все данные прочитаны
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Сколько байт данных осталось прочитать
+    pub fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Прочитать следующий результат канала channel, None - данные кончились
+    pub fn next_result(&mut self, channel: usize) -> Result<Option<u32>, Error> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+
+        let sample = self.counts[channel];
+        let prev = self.prevs[channel];
+        let result = match self.encoding {
+            SampleEncoding::Delta => {
+                let (v, rest) = self.data.split_at(4);
+                self.data = rest;
+                let v = [v[0], v[1], v[2], v[3]];
+                match prev {
+                    None => Some(u32::from_le_bytes(v)),
+                    Some(prev) => prev.my_checked_add_signed(i32::from_le_bytes(v)),
+                }
+            }
+            SampleEncoding::ZigzagVarint => {
+                let mut v = 0u64;
+                let mut len = 0;
+                loop {
+                    // разность двух u32 после zigzag занимает 33 бита,
+                    // в пятом байте остаются только старшие 5 бит
+                    let byte = match self.data.get(len) {
+                        Some(b) if len < 4 || (len == 4 && *b <= 0x1F) => *b,
+                        _ => return Err(Error::MalformedSample { channel, sample }),
+                    };
+                    v |= ((byte & 0x7F) as u64) << (7 * len);
+                    len += 1;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                }
+                self.data = &self.data[len..];
+                core::convert::TryFrom::try_from(prev.unwrap_or(0) as i64 + unzigzag(v)).ok()
+            }
+        };
+
+        let result = result.ok_or(Error::DeltaOverflow { channel, sample })?;
+        self.prevs[channel] = Some(result);
+        self.counts[channel] += 1;
+
+        Ok(Some(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, sample, unzigzag, zigzag, SampleDecoder, SampleEncoding};
+    use crate::Error;
+
+    #[test]
+    fn zigzag_roundtrip() {
+        for v in [0, 1, -1, 63, -64, 64, u32::MAX as i64, -(u32::MAX as i64)] {
+            assert_eq!(unzigzag(zigzag(v)), v);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+    }
+
+    #[test]
+    fn varint_size() {
+        assert_eq!(encode(SampleEncoding::ZigzagVarint, 0).1, 1);
+        assert_eq!(encode(SampleEncoding::ZigzagVarint, 63).1, 1);
+        assert_eq!(encode(SampleEncoding::ZigzagVarint, -64).1, 1);
+        assert_eq!(encode(SampleEncoding::ZigzagVarint, 64).1, 2);
+        assert_eq!(encode(SampleEncoding::ZigzagVarint, 8191).1, 2);
+        assert_eq!(encode(SampleEncoding::ZigzagVarint, 12_000_000).1, 4);
+        assert_eq!(encode(SampleEncoding::ZigzagVarint, i32::MIN as i64).1, 5);
+        assert_eq!(encode(SampleEncoding::ZigzagVarint, u32::MAX as i64).1, 5);
+        assert_eq!(
+            encode(SampleEncoding::ZigzagVarint, -(u32::MAX as i64)).1,
+            5
+        );
+        assert_eq!(encode(SampleEncoding::Delta, 0).1, 4);
+    }
+
+    #[test]
+    fn decode_channels() {
+        // FP: 300, 290; FT: 100
+        let mut data = alloc::vec::Vec::new();
+        for diff in [300, 100, -10] {
+            let (buf, len) = encode(SampleEncoding::ZigzagVarint, diff);
+            data.extend_from_slice(&buf[..len]);
+        }
+
+        let mut decoder = SampleDecoder::new(&data, SampleEncoding::ZigzagVarint, 2).unwrap();
+        assert_eq!(decoder.next_result(0), Ok(Some(300)));
+        assert_eq!(decoder.next_result(1), Ok(Some(100)));
+        assert_eq!(decoder.next_result(0), Ok(Some(290)));
+        assert!(decoder.is_empty());
+        assert_eq!(decoder.next_result(1), Ok(None));
+    }
+
+    #[test]
+    fn malformed_varint() {
+        // 3, затем оборванное продолжение
+        let data = [0x06, 0x80, 0x80];
+        let mut decoder = SampleDecoder::new(&data, SampleEncoding::ZigzagVarint, 1).unwrap();
+        assert_eq!(decoder.next_result(0), Ok(Some(3)));
+        assert_eq!(
+            decoder.next_result(0),
+            Err(Error::MalformedSample {
+                channel: 0,
+                sample: 1
+            })
+        );
+
+        // слишком длинное число
+        let data = [0x80; 6];
+        let mut decoder = SampleDecoder::new(&data, SampleEncoding::ZigzagVarint, 1).unwrap();
+        assert_eq!(
+            decoder.next_result(0),
+            Err(Error::MalformedSample {
+                channel: 0,
+                sample: 0
+            })
+        );
+    }
+
+    #[test]
+    fn full_range() {
+        // первый отсчет в Delta - сам результат, разности в ZigzagVarint - любые
+        let cases: [(SampleEncoding, &[u32]); 2] = [
+            (SampleEncoding::Delta, &[u32::MAX, u32::MAX - 5]),
+            (
+                SampleEncoding::ZigzagVarint,
+                &[u32::MAX, 0, u32::MAX - 1, 5],
+            ),
+        ];
+        for (encoding, results) in cases {
+            let mut data = alloc::vec::Vec::new();
+            let mut prev = None;
+            for result in results {
+                let (buf, len) = encode(encoding, sample(encoding, prev, *result).unwrap());
+                data.extend_from_slice(&buf[..len]);
+                prev = Some(*result);
+            }
+
+            let mut decoder = SampleDecoder::new(&data, encoding, 1).unwrap();
+            for result in results {
+                assert_eq!(decoder.next_result(0), Ok(Some(*result)));
+            }
+            assert!(decoder.is_empty());
+        }
+
+        // разность больше i32 в Delta не записать
+        assert_eq!(sample(SampleEncoding::Delta, Some(0), u32::MAX), None);
+    }
+
+    #[test]
+    fn delta_overflow() {
+        let mut data = alloc::vec::Vec::new();
+        for v in [5i64, -6] {
+            data.extend_from_slice(&encode(SampleEncoding::Delta, v).0[..4]);
+        }
+        let mut decoder = SampleDecoder::new(&data, SampleEncoding::Delta, 1).unwrap();
+        assert_eq!(decoder.next_result(0), Ok(Some(5)));
+        assert_eq!(
+            decoder.next_result(0),
+            Err(Error::DeltaOverflow {
+                channel: 0,
+                sample: 1
+            })
+        );
+    }
+
+    #[test]
+    fn delta_alignment() {
+        assert!(matches!(
+            SampleDecoder::new(&[0; 6], SampleEncoding::Delta, 1),
+            Err(Error::BadAlignment { len: 6, align: 4 })
+        ));
+    }
+}
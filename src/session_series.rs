@@ -0,0 +1,185 @@
+//! Непрерывный ряд отсчетов целого сеанса записи.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::csv_export::CsvWriter;
+use crate::{Chain, ChannelInfo, CsvOptions, PageData, Record};
+
+/// Отсчеты одного канала за весь сеанс
+pub struct ChannelSeries {
+    pub info: ChannelInfo,
+    /// отсчеты по возрастанию времени
+    pub records: Vec<Record>,
+}
+
+/// Промежуток времени, за который отсчетов нет
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct SeriesGap {
+    /// время, с которого ожидались отсчеты, мс
+    pub from_ms: u64,
+    /// время первого отсчета после разрыва, мс
+    pub to_ms: u64,
+}
+
+/// Отсчеты цепочки страниц, сшитые в один ряд на канал
+pub struct SessionSeries {
+    /// номер первого блока цепочки
+    pub first_block_id: u32,
+    /// время начала первой страницы, мс
+    pub start_ms: u64,
+    /// время старта записи по RTC из заголовков страниц, мс от 1970-01-01 UTC
+    pub rtc_start_ms: Option<u64>,
+    pub base_interval_ms: u32,
+    /// каналы в порядке их первого появления, одноименные каналы разных страниц объединяются
+    pub channels: Vec<ChannelSeries>,
+    /// разрывы по возрастанию времени
+    pub gaps: Vec<SeriesGap>,
+}
+
+impl SessionSeries {
+    /// Сшить отсчеты страниц цепочки, pages - в порядке цепочки.
+    ///
+    /// Отсчеты, не продолжающие ряд канала по времени (повтор тика на стыке
+    /// страниц, перекрытие), пропускаются. Разрыв отмечается, если страница
+    /// начинается позже, чем закончилась предыдущая страница с отсчетами,
+    /// в том числе когда между ними есть страницы без отсчетов.
+    pub fn from_pages(pages: &[PageData]) -> Self {
+        let mut res = Self {
+            first_block_id: pages.first().map_or(0, |p| p.header.this_block_id),
+            start_ms: pages.first().map_or(0, |p| p.header.timestamp),
+            rtc_start_ms: pages.iter().find_map(|p| p.header.rtc_start_ms),
+            base_interval_ms: pages.first().map_or(0, |p| p.header.base_interval_ms),
+            channels: Vec::new(),
+            gaps: Vec::new(),
+        };
+
+        let mut expected = None;
+        for page in pages {
+            let next_expected = page.expected_next_timestamp();
+            if next_expected.is_none() {
+                continue;
+            }
+
+            if let Some(from_ms) = expected {
+                if page.header.timestamp > from_ms {
+                    res.gaps.push(SeriesGap {
+                        from_ms,
+                        to_ms: page.header.timestamp,
+                    });
+                }
+            }
+            expected = next_expected;
+
+            for channel in page.channels.iter() {
+                let series = match res
+                    .channels
+                    .iter()
+                    .position(|c| c.info.name == channel.info.name)
+                {
+                    Some(i) => &mut res.channels[i],
+                    None => {
+                        res.channels.push(ChannelSeries {
+                            info: channel.info.clone(),
+                            records: Vec::new(),
+                        });
+                        res.channels.last_mut().unwrap()
+                    }
+                };
+
+                for r in channel.records.iter() {
+                    let stale = matches!(series.records.last(), Some(last) if r.timesstamp <= last.timesstamp);
+                    if !stale {
+                        series.records.push(*r);
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Сохранить весь ряд одним файлом CSV.
+    /// Значение канала держится до его следующего отсчета, разрыв
+    /// отмечается отдельной строкой и сбрасывает значения
+    pub fn save_as_csv<P: AsRef<Path>>(&self, file: P) -> std::io::Result<()> {
+        self.save_as_csv_with(file, &CsvOptions::default())
+    }
+
+    /// Сохранить весь ряд одним файлом CSV с заданными настройками
+    pub fn save_as_csv_with<P: AsRef<Path>>(
+        &self,
+        file: P,
+        options: &CsvOptions,
+    ) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(file)?);
+        self.write_csv(&mut file, options)?;
+        file.flush()
+    }
+
+    /// Записать весь ряд в CSV с заданными настройками в любой приемник
+    pub fn write_csv<W: Write>(&self, w: W, options: &CsvOptions) -> std::io::Result<()> {
+        let mut w = CsvWriter::new(w, options);
+
+        w.meta([
+            options
+                .tr("Сеанс с блока", "Session from block")
+                .to_string(),
+            self.first_block_id.to_string(),
+        ])?;
+        w.meta([
+            options.tr("Время начала", "Start time").to_string(),
+            options.format_timestamp(self.start_ms),
+        ])?;
+        w.meta([
+            options.tr("Базовый интервал", "Base interval").to_string(),
+            self.base_interval_ms.to_string(),
+            options.tr("мс.", "ms").to_string(),
+        ])?;
+
+        let channels = self
+            .channels
+            .iter()
+            .map(|c| c.info.clone())
+            .collect::<Vec<_>>();
+        w.header(&channels)?;
+
+        let mut samples = self
+            .channels
+            .iter()
+            .enumerate()
+            .flat_map(|(ch, c)| c.records.iter().map(move |r| (r.timesstamp, ch, *r)))
+            .collect::<Vec<_>>();
+        samples.sort_by_key(|(timestamp, ch, _)| (*timestamp, *ch));
+
+        let mut gaps = self.gaps.iter().peekable();
+        let mut current = vec![Record::default(); self.channels.len()];
+        let mut i = 0;
+        while i < samples.len() {
+            let timestamp = samples[i].0;
+
+            while let Some(gap) = gaps.next_if(|g| g.to_ms <= timestamp) {
+                w.gap(gap.from_ms, gap.to_ms)?;
+                current.iter_mut().for_each(|c| *c = Record::default());
+            }
+
+            while i < samples.len() && samples[i].0 == timestamp {
+                current[samples[i].1] = samples[i].2;
+                i += 1;
+            }
+            w.row(timestamp, &current)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Chain {
+    /// Сшить отсчеты страниц цепочки в один ряд
+    pub fn series(&self) -> SessionSeries {
+        SessionSeries::from_pages(&self.pages)
+    }
+}
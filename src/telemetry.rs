@@ -0,0 +1,313 @@
+//! Служебная телеметрия самописца: температура процессора и напряжение батареи.
+//!
+//! Оба значения записываются в заголовок каждой страницы, поэтому ряд
+//! телеметрии - точка на страницу во время её начала. По ряду напряжения
+//! прямой, подобранной по наименьшим квадратам, оценивается, когда батарея
+//! разрядится до заданного напряжения, а выходы значений за пороги
+//! отмечаются предупреждениями.
+
+use std::io::Write;
+
+use crate::csv_export::CsvWriter;
+use crate::{Chain, CsvOptions, PageData};
+
+const MS_PER_HOUR: f64 = 60.0 * 60.0 * 1000.0;
+
+/// Телеметрия из заголовка одной страницы
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TelemetryPoint {
+    /// время начала страницы, мс
+    pub timestamp: u64,
+    pub block_id: u32,
+    /// температура процессора, *С
+    pub t_cpu: f32,
+    /// напряжение батареи, В
+    pub v_bat: f32,
+}
+
+/// Ряд телеметрии сеанса
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Telemetry {
+    /// точки в порядке страниц
+    pub points: Vec<TelemetryPoint>,
+}
+
+/// Пороги предупреждений
+#[derive(Debug, Clone, Copy)]
+pub struct TelemetryThresholds {
+    /// напряжение батареи, ниже которого она считается разряженной, В
+    pub v_bat_low: f32,
+    /// наименьшая допустимая температура процессора, *С
+    pub t_cpu_min: f32,
+    /// наибольшая допустимая температура процессора, *С
+    pub t_cpu_max: f32,
+}
+
+impl Default for TelemetryThresholds {
+    fn default() -> Self {
+        Self {
+            v_bat_low: 3.2,
+            t_cpu_min: -40.0,
+            t_cpu_max: 85.0,
+        }
+    }
+}
+
+/// Какой порог нарушен
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum AlertKind {
+    /// напряжение батареи ниже [`TelemetryThresholds::v_bat_low`]
+    LowBattery,
+    /// температура процессора выше [`TelemetryThresholds::t_cpu_max`]
+    Overheat,
+    /// температура процессора ниже [`TelemetryThresholds::t_cpu_min`]
+    Overcool,
+}
+
+/// Значение вышло за порог, отмечается первая страница каждого выхода
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TelemetryAlert {
+    pub kind: AlertKind,
+    /// время начала страницы, мс
+    pub timestamp: u64,
+    pub block_id: u32,
+    /// напряжение или температура, вышедшие за порог
+    pub value: f32,
+}
+
+/// Оценка разряда батареи
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct BatteryForecast {
+    /// скорость изменения напряжения, В/ч
+    pub volts_per_hour: f64,
+    /// напряжение по прямой в момент последней точки, В
+    pub v_bat_now: f64,
+    /// когда напряжение дойдет до порога, мс от начала записи,
+    /// None - батарея не разряжается
+    pub depleted_at_ms: Option<u64>,
+    /// сколько осталось от последней точки до разряда, мс,
+    /// 0 - порог уже пройден, None - батарея не разряжается
+    pub remaining_ms: Option<u64>,
+}
+
+impl Telemetry {
+    /// Точка на каждую страницу, pages - в порядке записи
+    pub fn from_pages(pages: &[PageData]) -> Self {
+        Self {
+            points: pages
+                .iter()
+                .map(|p| TelemetryPoint {
+                    timestamp: p.header.timestamp,
+                    block_id: p.header.this_block_id,
+                    t_cpu: p.header.t_cpu,
+                    v_bat: p.header.v_bat,
+                })
+                .collect(),
+        }
+    }
+
+    /// Оценить, когда напряжение батареи опустится до cutoff_v.
+    ///
+    /// Прямая подбирается по точкам за последние window_ms до последней
+    /// точки, None - по всем точкам. Точки без измерения напряжения
+    /// пропускаются. None - меньше двух точек разного времени.
+    pub fn battery_forecast(
+        &self,
+        cutoff_v: f32,
+        window_ms: Option<u64>,
+    ) -> Option<BatteryForecast> {
+        let valid = || self.points.iter().filter(|p| p.v_bat.is_finite());
+        let last = valid().map(|p| p.timestamp).max()?;
+        let from = window_ms.map_or(0, |w| last.saturating_sub(w));
+        let points = valid()
+            .filter(|p| p.timestamp >= from)
+            .map(|p| ((p.timestamp - from) as f64, p.v_bat as f64))
+            .collect::<Vec<_>>();
+
+        let n = points.len() as f64;
+        let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+        let mean_v = points.iter().map(|(_, v)| v).sum::<f64>() / n;
+        let stt = points
+            .iter()
+            .map(|(t, _)| (t - mean_t) * (t - mean_t))
+            .sum::<f64>();
+        if points.len() < 2 || stt == 0.0 {
+            return None;
+        }
+        let slope = points
+            .iter()
+            .map(|(t, v)| (t - mean_t) * (v - mean_v))
+            .sum::<f64>()
+            / stt;
+
+        let at = |t: f64| mean_v + slope * (t - mean_t);
+        let now = (last - from) as f64;
+        let depleted_at = if slope < 0.0 {
+            Some(mean_t + (cutoff_v as f64 - mean_v) / slope)
+        } else {
+            None
+        };
+
+        Some(BatteryForecast {
+            volts_per_hour: slope * MS_PER_HOUR,
+            v_bat_now: at(now),
+            depleted_at_ms: depleted_at.map(|t| from + t.max(0.0).round() as u64),
+            remaining_ms: depleted_at.map(|t| (t - now).max(0.0).round() as u64),
+        })
+    }
+
+    /// Выходы значений за пороги в порядке точек. Пока значение остается
+    /// за порогом, повторные предупреждения не выдаются
+    pub fn alerts(&self, thresholds: &TelemetryThresholds) -> Vec<TelemetryAlert> {
+        let mut active = [false; 3];
+        let mut res = Vec::new();
+        for p in self.points.iter() {
+            let checks = [
+                (
+                    AlertKind::LowBattery,
+                    p.v_bat,
+                    p.v_bat < thresholds.v_bat_low,
+                ),
+                (AlertKind::Overheat, p.t_cpu, p.t_cpu > thresholds.t_cpu_max),
+                (AlertKind::Overcool, p.t_cpu, p.t_cpu < thresholds.t_cpu_min),
+            ];
+            for ((kind, value, violated), active) in checks.iter().zip(active.iter_mut()) {
+                if !value.is_finite() {
+                    continue;
+                }
+                if *violated && !*active {
+                    res.push(TelemetryAlert {
+                        kind: *kind,
+                        timestamp: p.timestamp,
+                        block_id: p.block_id,
+                        value: *value,
+                    });
+                }
+                *active = *violated;
+            }
+        }
+        res
+    }
+
+    /// Записать ряд в CSV: время, температура процессора и напряжение батареи
+    pub fn write_csv<W: Write>(&self, w: W, options: &CsvOptions) -> std::io::Result<()> {
+        let mut w = CsvWriter::new(w, options);
+        w.columns(&[
+            options.tr("Время", "Time").to_string(),
+            options.with_unit(
+                options.tr("Температура процессора", "CPU temperature"),
+                options.tr("*С", "*C"),
+            ),
+            options.with_unit(
+                options.tr("Заряд батареи", "Battery voltage"),
+                options.tr("В", "V"),
+            ),
+        ])?;
+        for p in self.points.iter() {
+            w.values(p.timestamp, &[p.t_cpu, p.v_bat])?;
+        }
+        Ok(())
+    }
+}
+
+impl Chain {
+    /// Телеметрия страниц цепочки
+    pub fn telemetry(&self) -> Telemetry {
+        Telemetry::from_pages(&self.pages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlertKind, Telemetry, TelemetryPoint, TelemetryThresholds};
+
+    const HOUR: u64 = 60 * 60 * 1000;
+
+    fn telemetry(points: &[(u64, f32, f32)]) -> Telemetry {
+        Telemetry {
+            points: points
+                .iter()
+                .enumerate()
+                .map(|(i, (timestamp, t_cpu, v_bat))| TelemetryPoint {
+                    timestamp: *timestamp,
+                    block_id: i as u32,
+                    t_cpu: *t_cpu,
+                    v_bat: *v_bat,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn forecast() {
+        // 0.1 В/ч от 3.6 В
+        let t = telemetry(&[
+            (0, 20.0, 3.6),
+            (HOUR, 20.0, 3.5),
+            (2 * HOUR, 20.0, f32::NAN),
+            (3 * HOUR, 20.0, 3.3),
+        ]);
+
+        let f = t.battery_forecast(3.0, None).unwrap();
+        assert!((f.volts_per_hour + 0.1).abs() < 1e-6);
+        assert!((f.v_bat_now - 3.3).abs() < 1e-6);
+        let hours = |ms: Option<u64>| ms.unwrap() as f64 / HOUR as f64;
+        assert!((hours(f.depleted_at_ms) - 6.0).abs() < 1e-3);
+        assert!((hours(f.remaining_ms) - 3.0).abs() < 1e-3);
+
+        // разряд начался через час, оценка по последнему часу
+        let late = telemetry(&[
+            (0, 20.0, 3.6),
+            (HOUR, 20.0, 3.6),
+            (2 * HOUR, 20.0, 3.5),
+            (3 * HOUR, 20.0, 3.4),
+        ]);
+        let f = late.battery_forecast(3.0, Some(HOUR)).unwrap();
+        assert!((f.volts_per_hour + 0.1).abs() < 1e-6);
+        assert!((hours(f.remaining_ms) - 4.0).abs() < 1e-3);
+        assert_eq!(t.battery_forecast(3.0, Some(HOUR)), None);
+
+        // порог уже пройден
+        assert_eq!(t.battery_forecast(3.4, None).unwrap().remaining_ms, Some(0));
+
+        // не разряжается или не по чему оценить
+        let charging = telemetry(&[(0, 20.0, 3.5), (HOUR, 20.0, 3.6)]);
+        assert_eq!(
+            charging.battery_forecast(3.0, None).unwrap().remaining_ms,
+            None
+        );
+        assert_eq!(
+            telemetry(&[(0, 20.0, 3.5)]).battery_forecast(3.0, None),
+            None
+        );
+        assert_eq!(
+            telemetry(&[(HOUR, 20.0, 3.5), (HOUR, 20.0, 3.4)]).battery_forecast(3.0, None),
+            None
+        );
+    }
+
+    #[test]
+    fn alerts() {
+        let t = telemetry(&[
+            (0, 20.0, 3.3),
+            (100, 90.0, 2.9),
+            (200, 95.0, 2.8),
+            (300, 20.0, 3.25),
+            (400, -45.0, 2.9),
+        ]);
+        let alerts = t
+            .alerts(&TelemetryThresholds::default())
+            .iter()
+            .map(|a| (a.kind, a.timestamp))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            alerts,
+            vec![
+                (AlertKind::LowBattery, 100),
+                (AlertKind::Overheat, 100),
+                (AlertKind::LowBattery, 400),
+                (AlertKind::Overcool, 400),
+            ]
+        );
+    }
+}
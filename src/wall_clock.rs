@@ -0,0 +1,91 @@
+//! Абсолютное время отсчетов.
+//!
+//! Время в страницах - миллисекунды от старта записи по часам самописца.
+//! [`WallClock`] переводит его во время UTC по времени старта, записанному
+//! в заголовке страниц версии 3 или известному при разборе. Если известно
+//! еще и точное время в конце записи, учитывается уход часов самописца:
+//! время растягивается или сжимается линейно между стартом и этой точкой.
+
+use crate::csv_export::iso8601;
+use crate::{DataPacketHeader, SessionSeries};
+
+/// Перевод времени от старта записи во время UTC
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct WallClock {
+    /// время старта записи, мс от 1970-01-01 UTC
+    pub start_unix_ms: u64,
+    /// мс UTC на миллисекунду часов самописца
+    pub rate: f64,
+}
+
+impl WallClock {
+    /// Часы без ухода, start_unix_ms - время старта записи, мс от 1970-01-01 UTC
+    pub fn new(start_unix_ms: u64) -> Self {
+        Self {
+            start_unix_ms,
+            rate: 1.0,
+        }
+    }
+
+    /// Время старта по RTC из заголовка страницы
+    pub fn from_header(header: &DataPacketHeader) -> Option<Self> {
+        header.rtc_start_ms.map(Self::new)
+    }
+
+    /// Учесть уход часов по точке синхронизации: в момент device_ms
+    /// часов самописца время UTC было unix_ms.
+    /// Точка в момент старта записи ничего не меняет
+    pub fn with_sync(self, device_ms: u64, unix_ms: u64) -> Self {
+        if device_ms == 0 {
+            return self;
+        }
+        Self {
+            rate: (unix_ms as f64 - self.start_unix_ms as f64) / device_ms as f64,
+            ..self
+        }
+    }
+
+    /// Уход часов самописца, миллионных долей: положительный - часы отстают
+    pub fn drift_ppm(&self) -> f64 {
+        (self.rate - 1.0) * 1e6
+    }
+
+    /// Время UTC, мс от 1970-01-01, по времени от старта записи
+    pub fn unix_ms(&self, device_ms: u64) -> u64 {
+        let offset = (device_ms as f64 * self.rate).round();
+        (self.start_unix_ms as f64 + offset).max(0.0) as u64
+    }
+
+    /// Время UTC в ISO-8601 с миллисекундами по времени от старта записи
+    pub fn iso8601(&self, device_ms: u64) -> String {
+        iso8601(self.unix_ms(device_ms))
+    }
+}
+
+impl SessionSeries {
+    /// Часы по времени старта RTC из заголовков страниц сеанса
+    pub fn wall_clock(&self) -> Option<WallClock> {
+        self.rtc_start_ms.map(WallClock::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WallClock;
+
+    #[test]
+    fn drift() {
+        let start = 1_614_834_367_089;
+        let clock = WallClock::new(start);
+        assert_eq!(clock.unix_ms(1500), start + 1500);
+        assert_eq!(clock.iso8601(1500), "2021-03-04T05:06:08.589Z");
+        assert_eq!(clock.with_sync(0, 0), clock);
+
+        // за сутки по часам самописца прошли сутки и 864 мс: часы отстают на 10 ppm
+        let day = 24 * 60 * 60 * 1000;
+        let clock = clock.with_sync(day, start + day + 864);
+        assert!((clock.drift_ppm() - 10.0).abs() < 1e-6);
+        assert_eq!(clock.unix_ms(day / 2), start + day / 2 + 432);
+        assert_eq!(clock.unix_ms(0), start);
+    }
+}
@@ -0,0 +1,51 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::Recording;
+    use self_recorder_packet::DataBlockUnPacker;
+
+    const BLOCK_SIZE: usize = 512;
+
+    /// Страницы по умолчанию: интервал 1 с, все каналы на каждом тике, без опорной частоты
+    fn record(ticks: u32) -> Vec<Vec<u8>> {
+        Recording::new(BLOCK_SIZE)
+            .base_interval(1000)
+            .ratios([1, 1])
+            .fref(0.0)
+            .record(ticks)
+    }
+
+    #[test]
+    fn decode_in_place() {
+        let storage = record(1000).concat();
+
+        for page in storage.chunks(BLOCK_SIZE) {
+            let borrowed = DataBlockUnPacker::try_new(page).unwrap();
+            // страница не скопирована
+            assert_eq!(borrowed.data().as_ptr(), page.as_ptr());
+            assert!(borrowed.verify());
+
+            let owned = DataBlockUnPacker::new(page.to_vec());
+            assert_eq!(borrowed.hader(), owned.hader());
+            assert_eq!(borrowed.unpack_data(), owned.unpack_data());
+        }
+    }
+
+    #[test]
+    fn unpackers_borrow_storage() {
+        fn headers(storage: &[u8]) -> Vec<DataBlockUnPacker<'_>> {
+            storage
+                .chunks(BLOCK_SIZE)
+                .map(DataBlockUnPacker::new)
+                .collect()
+        }
+
+        let storage = record(1000).concat();
+        let unpackers = headers(&storage);
+        assert!(unpackers.len() > 1);
+        for (i, u) in unpackers.iter().enumerate() {
+            assert_eq!(u.hader().unwrap().this_block_id, i as u32);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+#[cfg(feature = "unpacker")]
+mod test {
+    use std::path::Path;
+
+    use self_recorder_packet::{write_measurements_csv, Calibration, CsvOptions, Record};
+
+    fn readfile<P: AsRef<Path>>(path: P) -> Vec<f32> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .split('\n')
+            .map(|s| {
+                s.trim()
+                    .parse::<f32>()
+                    .unwrap_or_else(|_| panic!("failed to parse \"{}\"", s))
+            })
+            .collect()
+    }
+
+    fn records(freqs: &[f32]) -> Vec<Record> {
+        freqs
+            .iter()
+            .enumerate()
+            .map(|(i, freq)| Record {
+                timesstamp: i as u64 * 100,
+                freq: *freq,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// Отсчеты давления на переходных процессах, где результат прошивки
+    /// запаздывает на один отсчет: их ошибка не меньше P_MAX_ERROR,
+    /// но меньше P_OUTLIER_MAX_ERROR
+    const P_OUTLIERS: [usize; 15] = [
+        10003, 10004, 13790, 13791, 85969, 85970, 102785, 102786, 152189, 152190, 156312, 199126,
+        199127, 245408, 245409,
+    ];
+    const P_MAX_ERROR: f64 = 2e-3;
+    const P_OUTLIER_MAX_ERROR: f64 = 3.5e-3;
+    /// Две единицы младшего разряда f32 около 128 °C
+    const T_MAX_ERROR: f64 = 2e-5;
+
+    /// Давление и температура, пересчитанные прошивкой, совпадают с пересчетом
+    /// по коэффициентам датчика.
+    ///
+    /// Коэффициенты tests/calibration1.txt подобраны методом наименьших
+    /// квадратов в f64 по всем 258442 отсчетам FP1.txt/FT1.txt и эталонным
+    /// P1.txt/T1.txt: T - полином 3-й степени от Ft-Ft0, P - степени 2 и по
+    /// Fp-Fp0, и по Ft-Ft0, смещения Fp0 и Ft0 округлены. Коэффициенты записаны
+    /// с 17 значащими цифрами, чтобы f64 читался без потерь. Поэтому тест
+    /// проверяет пересчет по модели датчика, а не точность самой модели
+    #[test]
+    fn reference_data() {
+        let calibration = Calibration::load("tests/calibration1.txt").unwrap();
+
+        let fp = records(&readfile("tests/test_data/FP1.txt"));
+        let ft = records(&readfile("tests/test_data/FT1.txt"));
+        let p = readfile("tests/test_data/P1.txt");
+        let t = readfile("tests/test_data/T1.txt");
+
+        let result = calibration.convert(&fp, &ft);
+        assert_eq!(result.len(), p.len());
+
+        for (i, (m, t)) in result.iter().zip(t.iter()).enumerate() {
+            let error = (m.temperature as f64 - *t as f64).abs();
+            assert!(error < T_MAX_ERROR, "T[{}]: {}", i, error);
+        }
+        for (i, (m, p)) in result.iter().zip(p.iter()).enumerate() {
+            let error = (m.pressure as f64 - *p as f64).abs();
+            if P_OUTLIERS.contains(&i) {
+                assert!(
+                    (P_MAX_ERROR..P_OUTLIER_MAX_ERROR).contains(&error),
+                    "P[{}]: {}",
+                    i,
+                    error
+                );
+            } else {
+                assert!(error < P_MAX_ERROR, "P[{}]: {}", i, error);
+            }
+        }
+    }
+
+    #[test]
+    fn save_load_and_csv() {
+        let calibration = Calibration::load("tests/calibration1.txt").unwrap();
+
+        let dir = tempdir::TempDir::new("calibration").unwrap();
+        let file = dir.path().join("calibration.txt");
+        calibration.save(&file).unwrap();
+        assert_eq!(Calibration::load(&file).unwrap(), calibration);
+
+        std::fs::write(&file, "Fp0 = 1\nFt0 = 1\nT = 1\n").unwrap();
+        assert_eq!(
+            Calibration::load(&file).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+
+        // первые отсчеты FP1.txt и FT1.txt
+        let fp = records(&[1050.0967, 1050.0966]);
+        let ft = records(&[32524.334]);
+        let mut buf = Vec::new();
+        write_measurements_csv(
+            &calibration.convert(&fp, &ft),
+            &mut buf,
+            &CsvOptions {
+                precision: 3,
+                ..CsvOptions::plain()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "Time,Pressure,Temperature [*C]\n\
+             0,0.989,126.578\n\
+             100,0.989,126.578\n"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(feature = "unpacker")]
+mod test {
+    use std::path::Path;
+
+    use self_recorder_packet::{
+        fit_calibration, Calibration, CalibrationPoint, FitDegrees, Measurement, Record,
+    };
+
+    fn readfile<P: AsRef<Path>>(path: P) -> Vec<f32> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .split('\n')
+            .map(|s| {
+                s.trim()
+                    .parse::<f32>()
+                    .unwrap_or_else(|_| panic!("failed to parse \"{}\"", s))
+            })
+            .collect()
+    }
+
+    fn records(freqs: &[f32]) -> Vec<Record> {
+        freqs
+            .iter()
+            .enumerate()
+            .map(|(i, freq)| Record {
+                timesstamp: i as u64 * 100,
+                freq: *freq,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn median(mut values: Vec<f64>) -> f64 {
+        values.iter_mut().for_each(|v| *v = v.abs());
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        values[values.len() / 2]
+    }
+
+    /// Коэффициенты, подобранные по каждой 20-й точке P1.txt/T1.txt,
+    /// пересчитывают все точки так же, как коэффициенты датчика
+    #[test]
+    fn fit_reference_data() {
+        let fp = records(&readfile("tests/test_data/FP1.txt"));
+        let ft = records(&readfile("tests/test_data/FT1.txt"));
+        let p = readfile("tests/test_data/P1.txt");
+        let t = readfile("tests/test_data/T1.txt");
+
+        let reference = p
+            .iter()
+            .zip(t.iter())
+            .enumerate()
+            .step_by(20)
+            .map(|(i, (p, t))| Measurement {
+                timestamp: i as u64 * 100,
+                pressure: *p,
+                temperature: *t,
+            })
+            .collect::<Vec<_>>();
+        let points = CalibrationPoint::from_records(&reference, &fp, &ft);
+        assert_eq!(points.len(), reference.len());
+
+        let fit = fit_calibration(&points, &FitDegrees::default()).unwrap();
+        assert!(fit.temperature.max_abs() < 2e-3);
+        assert!(median(fit.pressure.values.clone()) < 5e-5);
+        assert!(fit.pressure.rms() < 5e-4);
+
+        let dir = tempdir::TempDir::new("calibration_fit").unwrap();
+        let file = dir.path().join("calibration.txt");
+        fit.calibration.save(&file).unwrap();
+        let fitted = Calibration::load(&file).unwrap();
+        let sensor = Calibration::load("tests/calibration1.txt").unwrap();
+
+        let fitted = fitted.convert(&fp, &ft);
+        let sensor = sensor.convert(&fp, &ft);
+        let dp = fitted
+            .iter()
+            .zip(sensor.iter())
+            .map(|(a, b)| a.pressure as f64 - b.pressure as f64)
+            .collect::<Vec<_>>();
+        let dt = fitted
+            .iter()
+            .zip(sensor.iter())
+            .map(|(a, b)| a.temperature as f64 - b.temperature as f64)
+            .fold(0.0, |max: f64, d| max.max(d.abs()));
+        assert!(median(dp) < 5e-5);
+        assert!(dt < 2e-3);
+    }
+}
@@ -0,0 +1,173 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::crc32;
+    use self_recorder_packet::{
+        analyze_continuity, build_chains, unpack_pages, ChainRecorder, DataBlockPacker,
+        DataPacketHeader, Error, SampleEncoding,
+    };
+
+    const BLOCK_SIZE: usize = 512;
+    const BASE_INTERVAL_MS: u32 = 100;
+    const START_MS: u64 = 60_000;
+
+    fn result(tick: u64, ch: usize) -> u32 {
+        10_000_000 + ((tick * 37 + ch as u64 * 11) % 1000) as u32
+    }
+
+    fn record(ticks: u64, ratios: [u32; 2]) -> Vec<Vec<u8>> {
+        let builder = DataBlockPacker::builder()
+            .set_ids(0, 0)
+            .set_timestamp(START_MS)
+            .set_targets([10_000_000, 10_000_000])
+            .set_write_cfg(BASE_INTERVAL_MS, ratios)
+            .set_fref(10_000_000.0)
+            .set_sample_encoding(SampleEncoding::ZigzagVarint)
+            .set_size(BLOCK_SIZE);
+
+        let mut pages = Vec::new();
+        let mut recorder = ChainRecorder::new(builder, |page| pages.push(page), crc32);
+        for tick in 0..ticks {
+            recorder.push(&[result(tick, 0), result(tick, 1)]).unwrap();
+        }
+        let _ = recorder.finish();
+
+        pages
+    }
+
+    #[test]
+    fn result_out_of_range() {
+        let builder = DataBlockPacker::builder()
+            .set_targets([10_000_000, 10_000_000])
+            .set_size(BLOCK_SIZE);
+        let mut pages = Vec::new();
+        let mut recorder = ChainRecorder::new(builder, |page| pages.push(page), crc32);
+        assert_eq!(recorder.push(&[10_000_000, 10_000_000]), Ok(false));
+        assert_eq!(
+            recorder.push(&[10_000_000, 3_000_000_000]),
+            Err(Error::ResultOutOfRange { channel: 1 })
+        );
+        assert_eq!(recorder.push(&[10_000_001, 10_000_001]), Ok(false));
+        let _ = recorder.finish();
+
+        // тик с ошибкой не записан ни в один канал
+        let pages = unpack_pages(&pages.concat(), BLOCK_SIZE, 0.0, false);
+        let page = pages[0].as_ref().unwrap();
+        for ch in 0..2 {
+            let results = page.channels[ch]
+                .records
+                .iter()
+                .map(|r| r.result)
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![10_000_000, 10_000_001]);
+        }
+    }
+
+    #[test]
+    fn results_near_u32_max() {
+        for encoding in [SampleEncoding::Delta, SampleEncoding::ZigzagVarint] {
+            let builder = DataBlockPacker::builder()
+                .set_targets([10_000_000, 10_000_000])
+                .set_sample_encoding(encoding)
+                .set_size(BLOCK_SIZE);
+            let expected = (0..500u32)
+                .map(|tick| [u32::MAX - tick % 7, u32::MAX - 1000 + tick])
+                .collect::<Vec<_>>();
+
+            let mut pages = Vec::new();
+            let mut recorder = ChainRecorder::new(builder, |page| pages.push(page), crc32);
+            for results in expected.iter() {
+                recorder.push(results).unwrap();
+            }
+            let _ = recorder.finish();
+
+            let pages = unpack_pages(&pages.concat(), BLOCK_SIZE, 0.0, false);
+            assert!(pages.len() > 1);
+            for (ch, _) in expected[0].iter().enumerate() {
+                let results = pages
+                    .iter()
+                    .flat_map(|p| p.as_ref().unwrap().channels[ch].records.clone())
+                    .map(|r| r.result)
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    results,
+                    expected.iter().map(|r| r[ch]).collect::<Vec<_>>(),
+                    "{:?}",
+                    encoding
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pages_are_chained() {
+        let pages = record(2000, [1, 2]);
+        assert!(pages.len() > 3);
+
+        let headers = pages
+            .iter()
+            .map(|p| DataPacketHeader::from_bytes(p).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(headers[0].is_initial());
+        assert_eq!(headers[0].timestamp, START_MS);
+        for (i, h) in headers.iter().enumerate().skip(1) {
+            assert_eq!(h.this_block_id, i as u32);
+            assert_eq!(h.prev_block_id, headers[i - 1].this_block_id);
+            assert!(h.timestamp > headers[i - 1].timestamp);
+            assert_eq!((h.timestamp - START_MS) % BASE_INTERVAL_MS as u64, 0);
+        }
+    }
+
+    #[test]
+    fn no_sample_lost_on_rollover() {
+        const TICKS: u64 = 3000;
+        let ratios = [1, 3];
+        let pages = record(TICKS, ratios).concat();
+
+        let pages = unpack_pages(&pages, BLOCK_SIZE, 0.0, false);
+        for (ch, ratio) in ratios.iter().enumerate() {
+            let samples = pages
+                .iter()
+                .flat_map(|p| {
+                    let p = p.as_ref().unwrap();
+                    assert!(p.consistant);
+                    p.channels[ch].records.clone()
+                })
+                .map(|r| (r.timesstamp, r.freq.to_bits()))
+                .collect::<Vec<_>>();
+
+            let ticks = samples
+                .iter()
+                .map(|(timestamp, freq)| {
+                    let tick = (timestamp - START_MS) / BASE_INTERVAL_MS as u64;
+                    assert_eq!(
+                        *freq,
+                        (10_000_000.0f32 * 10_000_000.0 / result(tick, ch) as f32).to_bits()
+                    );
+                    tick
+                })
+                .collect::<Vec<_>>();
+
+            // расписание отсчитывается от начала каждой страницы, так что
+            // интервал между отсчетами может сократиться, но не вырасти
+            // тик целиком в одной странице: время отсчетов не повторяется
+            assert_eq!(ticks[0], 0);
+            assert!(ticks
+                .windows(2)
+                .all(|w| w[1] > w[0] && w[1] - w[0] <= *ratio as u64));
+            assert!(TICKS - 1 - ticks.last().unwrap() < *ratio as u64);
+        }
+    }
+
+    #[test]
+    fn recorded_chain_is_continuous() {
+        let pages = record(3000, [2, 3]).concat();
+
+        let chains = build_chains(unpack_pages(&pages, BLOCK_SIZE, 0.0, false));
+        assert_eq!(chains.sessions.len(), 1);
+        assert!(chains.sessions[0].pages.len() > 3);
+        assert_eq!(analyze_continuity(&chains.sessions[0].pages), vec![]);
+    }
+}
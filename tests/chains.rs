@@ -0,0 +1,155 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::crc32;
+    use self_recorder_packet::{
+        build_chains, unpack_pages, ChainRecorder, Chains, DataBlockPacker, Fork, Merge,
+        MissingLink, SampleEncoding,
+    };
+
+    const BLOCK_SIZE: usize = 256;
+
+    /// Сеанс записи из pages страниц, result различает сеансы
+    fn session(pages: usize, result: u32) -> Vec<Vec<u8>> {
+        let builder = DataBlockPacker::builder()
+            .set_write_cfg(1000, [1, 1])
+            .set_sample_encoding(SampleEncoding::ZigzagVarint)
+            .set_size(BLOCK_SIZE);
+
+        let mut res = Vec::new();
+        let mut recorder = ChainRecorder::new(builder, |page| res.push(page), crc32);
+        let mut tick = 0u32;
+        while recorder.header().this_block_id < pages as u32 {
+            recorder.push(&[result + tick % 100, result + 1]).unwrap();
+            tick += 1;
+        }
+        drop(recorder);
+
+        res
+    }
+
+    fn chains(storage: &[Vec<u8>]) -> Chains {
+        build_chains(unpack_pages(&storage.concat(), BLOCK_SIZE, 0.0, false))
+    }
+
+    fn ids(positions: &[usize], storage: &[Vec<u8>]) -> Vec<u32> {
+        let pages = unpack_pages(&storage.concat(), BLOCK_SIZE, 0.0, false);
+        positions
+            .iter()
+            .map(|p| pages[*p].as_ref().unwrap().header.this_block_id)
+            .collect()
+    }
+
+    #[test]
+    fn sessions_in_storage_order() {
+        let mut storage = session(5, 1_000_000);
+        storage.extend(session(4, 2_000_000));
+
+        let chains = chains(&storage);
+        assert_eq!(chains.sessions.len(), 2);
+        assert_eq!(chains.sessions[0].positions, vec![0, 1, 2, 3, 4]);
+        assert_eq!(chains.sessions[1].positions, vec![5, 6, 7, 8]);
+        assert!(chains.sessions.iter().all(|s| s.is_session()));
+        assert!(chains.orphans.is_empty());
+        assert!(chains.forks.is_empty());
+        assert!(chains.merges.is_empty());
+        assert!(chains.missing_links.is_empty());
+    }
+
+    #[test]
+    fn flash_wrap_around() {
+        // сеанс начался в конце флеши и продолжился с её начала
+        let mut storage = session(6, 1_000_000);
+        storage.rotate_left(4);
+
+        let chains = chains(&storage);
+        assert_eq!(chains.sessions.len(), 1);
+        assert_eq!(chains.sessions[0].positions, vec![2, 3, 4, 5, 0, 1]);
+        assert_eq!(
+            ids(&chains.sessions[0].positions, &storage),
+            vec![0, 1, 2, 3, 4, 5]
+        );
+        assert!(chains.missing_links.is_empty());
+    }
+
+    #[test]
+    fn missing_page() {
+        let mut storage = session(6, 1_000_000);
+        storage.remove(2);
+
+        let chains = chains(&storage);
+        assert_eq!(chains.sessions.len(), 1);
+        assert_eq!(chains.sessions[0].positions, vec![0, 1]);
+        assert_eq!(chains.orphans.len(), 1);
+        assert_eq!(chains.orphans[0].positions, vec![2, 3, 4]);
+        assert!(!chains.orphans[0].is_session());
+        assert_eq!(
+            chains.missing_links,
+            vec![MissingLink {
+                position: 2,
+                prev_block_id: 2,
+                this_block_id: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn damaged_page_breaks_chain() {
+        let mut storage = session(5, 1_000_000);
+        storage[2] = vec![0xFF; BLOCK_SIZE];
+
+        let chains = chains(&storage);
+        assert_eq!(chains.sessions[0].positions, vec![0, 1]);
+        assert_eq!(chains.orphans[0].positions, vec![3, 4]);
+        assert_eq!(chains.missing_links[0].prev_block_id, 2);
+    }
+
+    #[test]
+    fn fork() {
+        // страница 1 записана еще раз в конец
+        let mut storage = session(4, 1_000_000);
+        storage.push(storage[1].clone());
+
+        let chains = chains(&storage);
+        assert_eq!(chains.sessions[0].positions, vec![0, 1, 2, 3]);
+        assert_eq!(
+            chains.forks,
+            vec![Fork {
+                position: 0,
+                this_block_id: 0,
+                candidates: vec![1, 4],
+                chosen: 1,
+            }]
+        );
+        assert_eq!(chains.orphans[0].positions, vec![4]);
+        assert!(chains.merges.is_empty());
+        assert!(chains.missing_links.is_empty());
+    }
+
+    #[test]
+    fn merge() {
+        // страница 1 записана еще раз в конец, за ней и за оригиналом
+        // может идти страница 2, отделенная стертой страницей
+        let mut storage = session(4, 1_000_000);
+        storage.insert(2, vec![0xFF; BLOCK_SIZE]);
+        storage.push(storage[1].clone());
+
+        let chains = chains(&storage);
+        assert_eq!(chains.sessions[0].positions, vec![0, 1, 3, 4]);
+        assert_eq!(
+            chains.merges,
+            vec![Merge {
+                position: 3,
+                this_block_id: 2,
+                predecessors: vec![1, 5],
+                chosen: Some(1),
+            }]
+        );
+        // копия без продолжения
+        assert_eq!(chains.orphans.len(), 1);
+        assert_eq!(chains.orphans[0].positions, vec![5]);
+        assert_eq!(chains.forks[0].candidates, vec![1, 5]);
+        assert!(chains.missing_links.is_empty());
+    }
+}
@@ -0,0 +1,568 @@
+mod common;
+
+#[cfg(feature = "cli")]
+mod test {
+    use std::path::Path;
+    use std::process::{Command, Output};
+
+    use crate::common::{record_chain, Recording};
+    use self_recorder_packet::{build_chains, unpack_pages, Calibration, DataBlockPackerBuilder};
+
+    const BLOCK_SIZE: usize = 512;
+
+    /// Сеанс записи, поврежденная страница и стертый хвост
+    fn write_dump(path: &Path, damaged: bool) {
+        write_dump_with(path, damaged, |builder| builder)
+    }
+
+    fn write_dump_with<F: FnOnce(DataBlockPackerBuilder) -> DataBlockPackerBuilder>(
+        path: &Path,
+        damaged: bool,
+        f: F,
+    ) {
+        let mut pages = record_chain(f(Recording::new(BLOCK_SIZE).builder()), 500);
+
+        if damaged {
+            pages[1][100] ^= 0xFF;
+        }
+        pages.push(vec![0xFF; BLOCK_SIZE]);
+        std::fs::write(path, pages.concat()).unwrap();
+    }
+
+    fn run(args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_self-recorder"))
+            .args(["--page-size", &BLOCK_SIZE.to_string()])
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn verify() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let good = dir.path().join("good.bin");
+        let bad = dir.path().join("bad.bin");
+        write_dump(&good, false);
+        write_dump(&bad, true);
+
+        let out = run(&["verify", good.to_str().unwrap()]);
+        assert_eq!(out.status.code(), Some(0));
+
+        let out = run(&["verify", bad.to_str().unwrap()]);
+        assert_eq!(out.status.code(), Some(1));
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(stdout.starts_with("1: Corrupt"));
+
+        let out = run(&["verify", dir.path().join("none.bin").to_str().unwrap()]);
+        assert_eq!(out.status.code(), Some(2));
+    }
+
+    #[test]
+    fn zero_page_size() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        write_dump(&dump, false);
+
+        for command in ["info", "stats"] {
+            let out = Command::new(env!("CARGO_BIN_EXE_self-recorder"))
+                .args(["-s", "0", command, dump.to_str().unwrap()])
+                .output()
+                .unwrap();
+            assert_eq!(out.status.code(), Some(2));
+            let stderr = String::from_utf8(out.stderr).unwrap();
+            assert!(stderr.contains("page size must be greater than zero"));
+        }
+    }
+
+    #[test]
+    fn info_and_stats() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        write_dump(&dump, false);
+
+        let out = run(&["info", dump.to_str().unwrap()]);
+        assert!(out.status.success());
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(stdout
+            .lines()
+            .next()
+            .unwrap()
+            .starts_with("0: Valid блок 0"));
+        assert!(stdout.lines().last().unwrap().contains("Erased"));
+
+        let out = run(&["stats", dump.to_str().unwrap()]);
+        assert!(out.status.success());
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(stdout.contains("Сеансов: 1,"));
+        assert!(stdout.contains("FP: отсчетов 500,"));
+    }
+
+    #[test]
+    fn truncated_dump() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        write_dump(&dump, false);
+        let mut data = std::fs::read(&dump).unwrap();
+        data.truncate(data.len() - BLOCK_SIZE / 2);
+        std::fs::write(&dump, data).unwrap();
+
+        // предупреждение не попадает в результат в stdout
+        let out = run(&["export", dump.to_str().unwrap(), "-o", "-", "--no-metadata"]);
+        assert!(out.status.success());
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(!stdout.contains("Warning"));
+        let stderr = String::from_utf8(out.stderr).unwrap();
+        assert!(stderr.contains("last page truncated to 256 bytes"));
+    }
+
+    #[test]
+    fn export() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        write_dump(&dump, false);
+
+        let merged = dir.path().join("merged");
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            merged.to_str().unwrap(),
+        ]);
+        assert!(out.status.success());
+        let files = std::fs::read_dir(&merged).unwrap().count();
+        assert_eq!(files, 1);
+        assert!(merged.join("session_000_000000.csv").exists());
+
+        let plain = dir.path().join("plain");
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            plain.to_str().unwrap(),
+            "--delimiter",
+            ",",
+            "--lang",
+            "en",
+            "--no-metadata",
+            "--time",
+            "ms",
+            "--precision",
+            "1",
+            "--unit-brackets",
+        ]);
+        assert!(out.status.success());
+        let csv = std::fs::read_to_string(plain.join("session_000_000000.csv")).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("Time,Pressure frequency [Hz],Temperature frequency [Hz]")
+        );
+        assert_eq!(lines.next(), Some("0,10000000.0,11111111.0"));
+
+        // в stdout то же, что в файл
+        let out = run(&["export", dump.to_str().unwrap(), "-o", "-"]);
+        assert!(out.status.success());
+        assert_eq!(
+            out.stdout,
+            std::fs::read(merged.join("session_000_000000.csv")).unwrap()
+        );
+
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            "-",
+            "--format",
+            "jsonl",
+        ]);
+        assert!(out.status.success());
+        let jsonl = String::from_utf8(out.stdout).unwrap();
+        assert_eq!(
+            jsonl.lines().next(),
+            Some(r#"{"session":0,"block":0,"channel":"FP","timestamp":0,"freq":10000000}"#)
+        );
+        // FP на каждом тике ровно один раз, FT по расписанию каждой страницы
+        assert_eq!(
+            jsonl
+                .lines()
+                .filter(|l| l.contains(r#""channel":"FP""#))
+                .count(),
+            500
+        );
+        let records = unpack_pages(&std::fs::read(&dump).unwrap(), BLOCK_SIZE, 0.0, false)
+            .iter()
+            .filter_map(|p| p.as_ref().ok())
+            .flat_map(|p| p.channels.iter().map(|c| c.records.len()))
+            .sum::<usize>();
+        assert_eq!(jsonl.lines().count(), records);
+
+        let json = dir.path().join("json");
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            json.to_str().unwrap(),
+            "--format",
+            "json",
+        ]);
+        assert!(out.status.success());
+        let text = std::fs::read_to_string(json.join("session_000_000000.json")).unwrap();
+        assert!(text.starts_with(r#"{"first_block_id":0,"start_ms":0,"rtc_start_ms":null,"base_interval_ms":100,"gaps":[],"channels":[{"name":"FP""#));
+
+        let pages = dir.path().join("pages");
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            pages.to_str().unwrap(),
+            "--per-page",
+        ]);
+        assert!(out.status.success());
+        assert!(pages.join("page_000000.csv").exists());
+        assert!(!pages
+            .join(format!("page_{:06}.csv", files_in_dump(&dump)))
+            .exists());
+    }
+
+    #[test]
+    fn export_jsonl_wrapped_flash() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        // запись дошла до конца флеши и продолжилась с её начала
+        let mut pages = record_chain(Recording::new(BLOCK_SIZE).builder(), 500);
+        pages.rotate_left(2);
+        std::fs::write(&dump, pages.concat()).unwrap();
+
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            "-",
+            "--format",
+            "jsonl",
+        ]);
+        assert!(out.status.success());
+        let jsonl = String::from_utf8(out.stdout).unwrap();
+        assert!(jsonl.lines().all(|l| l.starts_with(r#"{"session":0,"#)));
+
+        let fp = jsonl
+            .lines()
+            .filter(|l| l.contains(r#""channel":"FP""#))
+            .map(|l| {
+                let timestamp = l.split(r#""timestamp":"#).nth(1).unwrap();
+                timestamp.split(',').next().unwrap().parse::<u64>().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(fp.len(), 500);
+        assert_eq!(fp[0], 0);
+        assert!(fp.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn export_calibrated() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        write_dump(&dump, false);
+        // давление и температура - частоты в МГц
+        let calibration = dir.path().join("calibration.txt");
+        std::fs::write(
+            &calibration,
+            "Fp0 = 0\nFt0 = 0\nT = 0 1e-6\nP0 = 0\nP1 = 1e-6\n",
+        )
+        .unwrap();
+
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            "-",
+            "--calibration",
+            calibration.to_str().unwrap(),
+            "--lang",
+            "en",
+            "--no-metadata",
+            "--time",
+            "ms",
+            "--precision",
+            "3",
+        ]);
+        assert!(out.status.success());
+        let csv = String::from_utf8(out.stdout).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("Time;Pressure;Temperature, *C"));
+        assert_eq!(lines.next(), Some("0;10.000;11.111"));
+        assert_eq!(csv.lines().count(), 1 + 500);
+
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            "-",
+            "--format",
+            "jsonl",
+            "--calibration",
+            calibration.to_str().unwrap(),
+        ]);
+        assert_eq!(out.status.code(), Some(2));
+    }
+
+    #[test]
+    fn fit() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        write_dump(&dump, false);
+
+        // давление и температура - частоты в МГц, по каждому 10-му отсчету
+        let pages = unpack_pages(&std::fs::read(&dump).unwrap(), BLOCK_SIZE, 0.0, false);
+        let series = build_chains(pages).sessions[0].series();
+        let (fp, ft) = (&series.channels[0].records, &series.channels[1].records);
+        let reference = fp
+            .iter()
+            .step_by(10)
+            .map(|p| {
+                let t = ft
+                    .iter()
+                    .rev()
+                    .find(|t| t.timesstamp <= p.timesstamp)
+                    .unwrap();
+                format!(
+                    "{}; {}; {}\n",
+                    p.timesstamp,
+                    p.freq as f64 * 1e-6,
+                    t.freq as f64 * 1e-6
+                )
+            })
+            .collect::<String>();
+        let reference_file = dir.path().join("reference.txt");
+        std::fs::write(&reference_file, format!("# время; P; T\n{}", reference)).unwrap();
+
+        let calibration = dir.path().join("calibration.txt");
+        let args = [
+            "fit",
+            dump.to_str().unwrap(),
+            "--reference",
+            reference_file.to_str().unwrap(),
+            "-o",
+            calibration.to_str().unwrap(),
+            "--temperature-degree",
+            "1",
+            "--pressure-degree",
+            "1",
+            "--compensation-degree",
+            "0",
+        ];
+        let out = run(&args);
+        assert!(out.status.success());
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(stdout.starts_with("Точек: 50 из 50\n"));
+
+        let c = Calibration::load(&calibration).unwrap();
+        let p = fp[7];
+        let t = ft
+            .iter()
+            .rev()
+            .find(|t| t.timesstamp <= p.timesstamp)
+            .unwrap();
+        assert!((c.pressure(p.freq, t.freq) as f64 - p.freq as f64 * 1e-6).abs() < 1e-5);
+        assert!((c.temperature(t.freq) as f64 - t.freq as f64 * 1e-6).abs() < 1e-5);
+
+        let mut args = args.to_vec();
+        args.extend(["--session", "1"]);
+        assert_eq!(run(&args).status.code(), Some(2));
+    }
+
+    #[test]
+    fn export_wall_clock() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        let rtc = dir.path().join("rtc.bin");
+        write_dump(&dump, false);
+        write_dump_with(&rtc, false, |builder| {
+            builder.set_rtc_start(1_614_834_367_089)
+        });
+
+        let export = |dump: &Path, args: &[&str]| {
+            let mut all = vec!["export", dump.to_str().unwrap(), "-o", "-", "--no-metadata"];
+            all.extend_from_slice(args);
+            run(&all)
+        };
+        let second_line = |out: Output| {
+            assert!(out.status.success());
+            String::from_utf8(out.stdout)
+                .unwrap()
+                .lines()
+                .nth(1)
+                .unwrap()
+                .to_string()
+        };
+
+        // время старта из заголовков страниц
+        assert_eq!(
+            second_line(export(&rtc, &["--time", "iso"])),
+            "2021-03-04T05:06:07.089Z;10000000.000000;11111111.000000"
+        );
+        // заданное время старта и часы, отстающие вдвое
+        assert_eq!(
+            second_line(export(
+                &dump,
+                &[
+                    "--time",
+                    "unix",
+                    "--origin-unix-ms",
+                    "1000",
+                    "--sync",
+                    "100:1200"
+                ]
+            )),
+            "1000;10000000.000000;11111111.000000"
+        );
+        let out = export(
+            &dump,
+            &[
+                "--time",
+                "unix",
+                "--origin-unix-ms",
+                "1000",
+                "--sync",
+                "100:1200",
+            ],
+        );
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(stdout.lines().nth(2).unwrap().starts_with("1200;"));
+
+        // без времени старта абсолютное время не получить
+        assert_eq!(export(&dump, &["--time", "iso"]).status.code(), Some(2));
+
+        let out = export(&rtc, &["--format", "jsonl"]);
+        let jsonl = String::from_utf8(out.stdout).unwrap();
+        assert!(jsonl
+            .lines()
+            .next()
+            .unwrap()
+            .contains(r#""timestamp":0,"unix_ms":1614834367089,"#));
+    }
+
+    #[test]
+    fn telemetry() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        write_dump(&dump, false);
+
+        let out = run(&["telemetry", dump.to_str().unwrap(), "--t-cpu-min", "1"]);
+        assert!(out.status.success());
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        let lines = stdout.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with("Сеанс 0: страниц "));
+        assert!(lines[1].ends_with("не разряжается"));
+        // в тестовой записи телеметрия нулевая
+        assert!(lines[2].ends_with("блок 0: LowBattery 0"));
+        assert!(lines[3].ends_with("блок 0: Overcool 0"));
+
+        let out = run(&["telemetry", dump.to_str().unwrap(), "--series"]);
+        assert!(out.status.success());
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        let mut lines = stdout.lines();
+        assert_eq!(lines.next(), Some("# Сеанс 0"));
+        assert_eq!(
+            lines.next(),
+            Some("Время;Температура процессора, *С;Заряд батареи, В")
+        );
+        assert_eq!(lines.next(), Some("00:00:00.000;0.000000;0.000000"));
+
+        let out = run(&[
+            "telemetry",
+            dump.to_str().unwrap(),
+            "--window-min",
+            &u64::MAX.to_string(),
+        ]);
+        assert_eq!(out.status.code(), Some(2));
+        let stderr = String::from_utf8(out.stderr).unwrap();
+        assert!(stderr.contains("--window-min is too large"));
+    }
+
+    #[test]
+    fn fref_session() {
+        let dir = tempdir::TempDir::new("cli").unwrap();
+        let dump = dir.path().join("dump.bin");
+        write_dump(&dump, false);
+
+        let out = run(&["info", dump.to_str().unwrap()]);
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        assert!(stdout
+            .lines()
+            .next()
+            .unwrap()
+            .contains("f_ref 10000000 (расчет по 10000000 Header)"));
+
+        let fp_min = |args: &[&str]| {
+            let out = run(args);
+            assert!(out.status.success());
+            let stdout = String::from_utf8(out.stdout).unwrap();
+            let line = stdout
+                .lines()
+                .find(|l| l.trim_start().starts_with("FP:"))
+                .unwrap()
+                .to_string();
+            line.split("мин. ")
+                .nth(1)
+                .unwrap()
+                .split(' ')
+                .next()
+                .unwrap()
+                .parse::<f64>()
+                .unwrap()
+        };
+        let header = fp_min(&["stats", dump.to_str().unwrap()]);
+        let overridden = fp_min(&[
+            "stats",
+            dump.to_str().unwrap(),
+            "--fref-session",
+            "0=20000000",
+        ]);
+        assert!((overridden / header - 2.0).abs() < 1e-6);
+
+        let out = run(&["stats", dump.to_str().unwrap(), "--fref-session", "0:1"]);
+        assert_eq!(out.status.code(), Some(2));
+
+        // JSON Lines и постраничный экспорт тоже пересчитываются
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            "-",
+            "--format",
+            "jsonl",
+            "--fref-session",
+            "0=20000000",
+        ]);
+        assert!(out.status.success());
+        let jsonl = String::from_utf8(out.stdout).unwrap();
+        assert_eq!(
+            jsonl.lines().next(),
+            Some(r#"{"session":0,"block":0,"channel":"FP","timestamp":0,"freq":20000000}"#)
+        );
+
+        let pages = dir.path().join("pages");
+        let out = run(&[
+            "export",
+            dump.to_str().unwrap(),
+            "-o",
+            pages.to_str().unwrap(),
+            "--per-page",
+            "--format",
+            "json",
+            "--fref-session",
+            "0=20000000",
+        ]);
+        assert!(out.status.success());
+        for position in 0..files_in_dump(&dump) {
+            let text =
+                std::fs::read_to_string(pages.join(format!("page_{:06}.json", position))).unwrap();
+            assert!(text.contains(r#""f_ref_used":20000000,"f_ref_source":"override""#));
+        }
+    }
+
+    fn files_in_dump(dump: &Path) -> usize {
+        std::fs::metadata(dump).unwrap().len() as usize / BLOCK_SIZE - 1
+    }
+}
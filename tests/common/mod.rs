@@ -0,0 +1,114 @@
+//! Общие помощники интеграционных тестов: запись тестовых цепочек страниц.
+#![allow(dead_code)]
+
+use self_recorder_packet::{
+    ChainRecorder, DataBlockPacker, DataBlockPackerBuilder, SampleEncoding,
+};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Результаты счетчиков каналов FP и FT на тике tick
+pub fn results(tick: u32) -> [u32; 2] {
+    [10_000_000 + tick % 50, 9_000_000 + tick % 70]
+}
+
+/// Настройки записи тестовой цепочки с каналами FP и FT
+#[derive(Clone, Copy)]
+pub struct Recording {
+    pub page_size: usize,
+    pub base_interval_ms: u32,
+    /// делители базового интервала FP и FT
+    pub ratios: [u32; 2],
+    pub fref: f32,
+    pub encoding: SampleEncoding,
+}
+
+impl Recording {
+    /// Интервал 100 мс, делители 1 и 2, опорная частота 10 МГц, [`SampleEncoding::Delta`]
+    pub fn new(page_size: usize) -> Self {
+        Self {
+            page_size,
+            base_interval_ms: 100,
+            ratios: [1, 2],
+            fref: 10_000_000.0,
+            encoding: SampleEncoding::Delta,
+        }
+    }
+
+    pub fn base_interval(mut self, base_interval_ms: u32) -> Self {
+        self.base_interval_ms = base_interval_ms;
+        self
+    }
+
+    pub fn ratios(mut self, ratios: [u32; 2]) -> Self {
+        self.ratios = ratios;
+        self
+    }
+
+    pub fn fref(mut self, fref: f32) -> Self {
+        self.fref = fref;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: SampleEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn builder(&self) -> DataBlockPackerBuilder {
+        DataBlockPacker::builder()
+            .set_targets([10_000_000, 10_000_000])
+            .set_write_cfg(self.base_interval_ms, self.ratios)
+            .set_fref(self.fref)
+            .set_sample_encoding(self.encoding)
+            .set_size(self.page_size)
+    }
+
+    /// Записать ticks тиков, результат - страницы в порядке записи
+    pub fn record(&self, ticks: u32) -> Vec<Vec<u8>> {
+        record_chain(self.builder(), ticks)
+    }
+
+    /// Первая заполненная страница цепочки
+    pub fn first_page(&self) -> Vec<u8> {
+        let mut pages = Vec::new();
+        let mut recorder = ChainRecorder::new(self.builder(), |page| pages.push(page), crc32);
+        for tick in 0.. {
+            if recorder.push(&results(tick)).unwrap() {
+                break;
+            }
+        }
+        drop(recorder);
+
+        pages.remove(0)
+    }
+}
+
+/// Записать ticks тиков со страницами по builder
+pub fn record_chain(builder: DataBlockPackerBuilder, ticks: u32) -> Vec<Vec<u8>> {
+    record_chain_with(builder, ticks, |_| (0.0, 0.0))
+}
+
+/// Записать ticks тиков, telemetry - температура процессора и заряд батареи
+/// на каждом тике
+pub fn record_chain_with<F: FnMut(u32) -> (f32, f32)>(
+    builder: DataBlockPackerBuilder,
+    ticks: u32,
+    mut telemetry: F,
+) -> Vec<Vec<u8>> {
+    let mut pages = Vec::new();
+    let mut recorder = ChainRecorder::new(builder, |page| pages.push(page), crc32);
+    for tick in 0..ticks {
+        let (t_cpu, v_bat) = telemetry(tick);
+        recorder.set_tcpu(t_cpu);
+        recorder.set_vbat(v_bat);
+        recorder.push(&results(tick)).unwrap();
+    }
+    let _ = recorder.finish();
+
+    pages
+}
@@ -0,0 +1,67 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use std::io::{self, Write};
+
+    use crate::common::Recording;
+    use self_recorder_packet::{build_chains, unpack_pages, CsvOptions};
+
+    const BLOCK_SIZE: usize = 512;
+
+    fn record() -> Vec<u8> {
+        Recording::new(BLOCK_SIZE).record(300).concat()
+    }
+
+    /// Приемник, отказывающий после limit байт
+    struct Limited {
+        limit: usize,
+    }
+
+    impl Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.limit < buf.len() {
+                return Err(io::Error::from(io::ErrorKind::WriteZero));
+            }
+            self.limit -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writer_and_file_match() {
+        let dir = tempdir::TempDir::new("csv_export").unwrap();
+        let pages = unpack_pages(&record(), BLOCK_SIZE, 0.0, false);
+
+        for options in [CsvOptions::default(), CsvOptions::plain()] {
+            let page = pages[1].as_ref().unwrap();
+            let mut buf = Vec::new();
+            page.write_csv(&mut buf, &options).unwrap();
+            page.save_as_csv_with(dir.path().join("page.csv"), &options)
+                .unwrap();
+            assert_eq!(buf, std::fs::read(dir.path().join("page.csv")).unwrap());
+            assert!(!buf.is_empty());
+        }
+
+        let chains = build_chains(pages);
+        let series = chains.sessions[0].series();
+        let mut buf = Vec::new();
+        series.write_csv(&mut buf, &CsvOptions::default()).unwrap();
+        series.save_as_csv(dir.path().join("session.csv")).unwrap();
+        assert_eq!(buf, std::fs::read(dir.path().join("session.csv")).unwrap());
+    }
+
+    #[test]
+    fn write_error_is_reported() {
+        let pages = unpack_pages(&record(), BLOCK_SIZE, 0.0, false);
+        let chains = build_chains(pages);
+        let series = chains.sessions[0].series();
+
+        let res = series.write_csv(Limited { limit: 1000 }, &CsvOptions::default());
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::WriteZero);
+    }
+}
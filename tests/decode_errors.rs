@@ -0,0 +1,201 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::crc32;
+    use self_recorder_packet::{
+        unpack_pages, DataBlockPacker, DataBlockUnPacker, Error, PageHeader, PushResult,
+        SampleEncoding,
+    };
+
+    const BLOCK_SIZE: usize = 4096;
+
+    /// Заполнить страницу: сначала значения first, затем повторяющиеся разности fill
+    fn make_page<T: Copy>(mut packer: DataBlockPacker, first: &[T], fill: i32) -> Vec<u8> {
+        for v in first {
+            if packer.push_val(*v) == PushResult::Full {
+                return packer.to_result_full(crc32).unwrap();
+            }
+        }
+
+        while packer.push_val(fill) != PushResult::Full {}
+        packer.to_result_full(crc32).unwrap()
+    }
+
+    fn packer() -> DataBlockPacker {
+        DataBlockPacker::builder()
+            .set_targets([12_000_000, 11_000_000])
+            .set_size(BLOCK_SIZE)
+            .build()
+    }
+
+    fn good_page() -> Vec<u8> {
+        make_page(packer(), &[12_000_000i32, 11_000_000], 3)
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let mut page = good_page();
+        assert_eq!(DataBlockUnPacker::new(page.clone()).check(), Ok(()));
+
+        let data_offset = PageHeader::parse(&page).unwrap().data_offset;
+        page[data_offset + 10] ^= 0x80;
+        assert_eq!(
+            DataBlockUnPacker::new(page.clone()).check(),
+            Err(Error::DataCrcMismatch)
+        );
+
+        // младший байт timestamp
+        page[16] ^= 0x01;
+        assert_eq!(
+            DataBlockUnPacker::new(page).check(),
+            Err(Error::HeaderCrcMismatch)
+        );
+    }
+
+    #[test]
+    fn damaged_page_is_not_decoded() {
+        let mut page = good_page();
+        let data_offset = PageHeader::parse(&page).unwrap().data_offset;
+        page[data_offset + 10] ^= 0x80;
+
+        let pages = unpack_pages(&page, BLOCK_SIZE, 0.0, false);
+        let page = pages[0].as_ref().unwrap();
+        assert!(!page.consistant);
+        assert!(page.channels.iter().all(|c| c.records.is_empty()));
+    }
+
+    #[test]
+    fn data_past_page_end() {
+        let mut page = good_page();
+        let data_offset = PageHeader::parse(&page).unwrap().data_offset;
+        // поле data_len
+        page[40..44].copy_from_slice(&5000u32.to_le_bytes());
+
+        let unpacker = DataBlockUnPacker::new(page);
+        assert_eq!(
+            unpacker.unpack_data(),
+            Err(Error::ShortPage {
+                need: data_offset + 5000,
+                got: BLOCK_SIZE
+            })
+        );
+        assert_eq!(
+            unpacker.check(),
+            Err(Error::ShortPage {
+                need: data_offset + 5000,
+                got: BLOCK_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn bad_alignment() {
+        // один лишний байт перед значениями u32
+        let page = make_page(packer(), &[0u8], 3);
+
+        let unpacker = DataBlockUnPacker::new(page.clone());
+        let len = unpacker.unpack_data().unwrap().len();
+        assert_eq!(len % 4, 1);
+        assert_eq!(
+            unpacker.unpack_as::<u32>(),
+            Err(Error::BadAlignment { len, align: 4 })
+        );
+
+        let pages = unpack_pages(&page, BLOCK_SIZE, 0.0, false);
+        assert_eq!(
+            pages[0].as_ref().err(),
+            Some(&Error::BadAlignment { len, align: 4 })
+        );
+    }
+
+    #[test]
+    fn delta_overflow() {
+        // второй отсчет канала FT уходит ниже нуля
+        let page = make_page(packer(), &[12_000_000i32, 11_000_000, 3, -12_000_000], 3);
+
+        let pages = unpack_pages(&page, BLOCK_SIZE, 0.0, false);
+        assert_eq!(
+            pages[0].as_ref().err(),
+            Some(&Error::DeltaOverflow {
+                channel: 1,
+                sample: 1
+            })
+        );
+    }
+
+    #[test]
+    fn delta_min() {
+        // разность i32::MIN, модуль которой не помещается в i32
+        let page = make_page(packer(), &[5, 5, i32::MIN], 3);
+        let pages = unpack_pages(&page, BLOCK_SIZE, 0.0, false);
+        assert_eq!(
+            pages[0].as_ref().err(),
+            Some(&Error::DeltaOverflow {
+                channel: 0,
+                sample: 1
+            })
+        );
+
+        // то же в zigzag + LEB128
+        let mut packer = DataBlockPacker::builder()
+            .set_targets([12_000_000, 11_000_000])
+            .set_sample_encoding(SampleEncoding::ZigzagVarint)
+            .set_size(BLOCK_SIZE)
+            .build();
+        packer.push_bytes(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+        packer.finish();
+        let page = packer.to_result_full(crc32).unwrap();
+        let pages = unpack_pages(&page, BLOCK_SIZE, 0.0, false);
+        assert_eq!(
+            pages[0].as_ref().err(),
+            Some(&Error::DeltaOverflow {
+                channel: 0,
+                sample: 0
+            })
+        );
+    }
+
+    #[test]
+    fn zero_interleave() {
+        let packer = DataBlockPacker::builder()
+            .set_write_cfg(1000, [1, 0])
+            .set_size(BLOCK_SIZE)
+            .build();
+        let page = make_page(packer, &[12_000_000i32], 3);
+
+        // канал с нулевым делителем не опрашивается
+        let pages = unpack_pages(&page, BLOCK_SIZE, 0.0, false);
+        let page = pages[0].as_ref().unwrap();
+        assert!(!page.channels[0].records.is_empty());
+        assert!(page.channels[1].records.is_empty());
+
+        let packer = DataBlockPacker::builder()
+            .set_write_cfg(1000, [0, 0])
+            .set_size(BLOCK_SIZE)
+            .build();
+        let page = make_page(packer, &[12_000_000i32], 3);
+
+        let pages = unpack_pages(&page, BLOCK_SIZE, 0.0, false);
+        assert_eq!(pages[0].as_ref().err(), Some(&Error::ZeroInterleave));
+    }
+
+    #[test]
+    fn bad_page_does_not_stop_others() {
+        let mut data = good_page();
+        data.extend(make_page(packer(), &[5i32, 5, -6], 3));
+        data.extend(good_page());
+
+        let pages = unpack_pages(&data, BLOCK_SIZE, 0.0, false);
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0].as_ref().unwrap().consistant);
+        assert_eq!(
+            pages[1].as_ref().err(),
+            Some(&Error::DeltaOverflow {
+                channel: 0,
+                sample: 1
+            })
+        );
+        assert!(pages[2].as_ref().unwrap().consistant);
+    }
+}
@@ -216,7 +216,8 @@ mod test {
         let personal_diffed_merged = {
             let mut prev_p = 0i32;
             let mut prev_t = 0i32;
-            res_fp.iter()
+            res_fp
+                .iter()
                 .zip(res_ft.iter())
                 .enumerate()
                 .flat_map(|(i, (fp, ft))| {
@@ -469,14 +470,14 @@ Compression: Best {}: {:.2}%, Worst: {}: {:.2} %
             .enumerate()
             .fold(vec![], |mut acc, (pocket_id, block)| {
                 let unpacker = DataBlockUnPacker::new(block.0);
-                let h = unpacker.hader();
+                let h = unpacker.hader().unwrap();
                 assert_eq!(pocket_id as u32, h.this_block_id);
                 assert_eq!(
                     (pocket_id as u32).checked_sub(1).unwrap_or_default(),
                     h.prev_block_id
                 );
 
-                let mut data = unpacker.unpack_as::<u32>();
+                let mut data = unpacker.unpack_as::<u32>().unwrap();
                 acc.append(&mut data);
                 acc
             })
@@ -488,14 +489,14 @@ Compression: Best {}: {:.2}%, Worst: {}: {:.2} %
             .enumerate()
             .fold(vec![], |mut acc, (pocket_id, block)| {
                 let unpacker = DataBlockUnPacker::new(block.0);
-                let h = unpacker.hader();
+                let h = unpacker.hader().unwrap();
                 assert_eq!(pocket_id as u32, h.this_block_id);
                 assert_eq!(
                     (pocket_id as u32).checked_sub(1).unwrap_or_default(),
                     h.prev_block_id
                 );
 
-                let mut data = unpacker.unpack_as::<u32>();
+                let mut data = unpacker.unpack_as::<u32>().unwrap();
                 let mut prev = data[0];
                 data[1..].iter_mut().for_each(|v| {
                     let this_value = prev
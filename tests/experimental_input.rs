@@ -72,7 +72,7 @@ mod test {
         };
 
         let unpacker = DataBlockUnPacker::new(result);
-        let unpacked = unpacker.unpack_as();
+        let unpacked = unpacker.unpack_as().unwrap();
         let exp_fragment = experimental_data
             .iter()
             .cloned()
@@ -302,14 +302,14 @@ Compression: Best {}: {:.2}%, Worst: {}: {:.2} %
             .enumerate()
             .fold(vec![], |mut acc, (pocket_id, block)| {
                 let unpacker = DataBlockUnPacker::new(block.0);
-                let h = unpacker.hader();
+                let h = unpacker.hader().unwrap();
                 assert_eq!(pocket_id as u32, h.this_block_id);
                 assert_eq!(
                     (pocket_id as u32).checked_sub(1).unwrap_or_default(),
                     h.prev_block_id
                 );
 
-                let mut data = unpacker.unpack_as::<f32>();
+                let mut data = unpacker.unpack_as::<f32>().unwrap();
                 acc.append(&mut data);
                 acc
             })
@@ -321,14 +321,14 @@ Compression: Best {}: {:.2}%, Worst: {}: {:.2} %
             .enumerate()
             .fold(vec![], |mut acc, (pocket_id, block)| {
                 let unpacker = DataBlockUnPacker::new(block.0);
-                let h = unpacker.hader();
+                let h = unpacker.hader().unwrap();
                 assert_eq!(pocket_id as u32, h.this_block_id);
                 assert_eq!(
                     (pocket_id as u32).checked_sub(1).unwrap_or_default(),
                     h.prev_block_id
                 );
 
-                let mut data = unpacker.unpack_as::<f32>();
+                let mut data = unpacker.unpack_as::<f32>().unwrap();
                 let mut prev = data[0];
                 data[1..].iter_mut().for_each(|v| {
                     let this_value = prev + *v;
@@ -0,0 +1,171 @@
+#[cfg(feature = "unpacker")]
+mod test {
+    use self_recorder_packet::{
+        ChecksumStatus, DataBlockPacker, DataBlockPackerBuilder, DataBlockUnPacker,
+        DataPacketHeader, Error, FormatVersion, PageHeader, PageIntegrity, PushResult, PAGE_MAGIC,
+    };
+
+    const BLOCK_SIZE: usize = 4096;
+
+    fn builder() -> DataBlockPackerBuilder {
+        DataBlockPacker::builder()
+            .set_ids(11, 12)
+            .set_timestamp(600_000)
+            .set_targets([12_000_000, 11_000_000])
+            .set_size(BLOCK_SIZE)
+    }
+
+    fn make_page() -> (Vec<u8>, Vec<u32>) {
+        make_page_with(builder())
+    }
+
+    fn make_page_with(builder: DataBlockPackerBuilder) -> (Vec<u8>, Vec<u32>) {
+        let mut packer = builder.build();
+
+        let mut values = Vec::new();
+        for i in 0u32.. {
+            let v = 12_000_000 + (i % 97) * 3;
+            values.push(v);
+            if packer.push_val(v) == PushResult::Full {
+                break;
+            }
+        }
+
+        (
+            packer
+                .to_result_full(|data| {
+                    let mut hasher = crc32fast::Hasher::new();
+                    hasher.update(data);
+                    hasher.finalize()
+                })
+                .unwrap(),
+            values,
+        )
+    }
+
+    /// Страница в формате v1: те же поля заголовка и данные, но без сигнатуры,
+    /// версии, длины, таблицы каналов и CRC заголовка
+    fn downgrade_to_v1(page: &[u8]) -> Vec<u8> {
+        let page_header = PageHeader::parse(page).unwrap();
+        let h = &page_header.header;
+        assert_eq!(h.channels.len(), 2);
+
+        let mut res = Vec::new();
+        res.extend_from_slice(&h.prev_block_id.to_le_bytes());
+        res.extend_from_slice(&h.this_block_id.to_le_bytes());
+        res.extend_from_slice(&h.timestamp.to_le_bytes());
+        res.extend_from_slice(&h.f_ref.to_le_bytes());
+        res.extend_from_slice(&h.channels[0].target.to_le_bytes());
+        res.extend_from_slice(&h.channels[1].target.to_le_bytes());
+        res.extend_from_slice(&h.base_interval_ms.to_le_bytes());
+        res.extend_from_slice(&h.channels[0].interleave_ratio.to_le_bytes());
+        res.extend_from_slice(&h.channels[1].interleave_ratio.to_le_bytes());
+        res.extend_from_slice(&h.t_cpu.to_le_bytes());
+        res.extend_from_slice(&h.v_bat.to_le_bytes());
+        res.extend_from_slice(&h.data_len.to_le_bytes());
+        res.extend_from_slice(&h.data_crc32.to_le_bytes());
+        assert_eq!(res.len(), DataPacketHeader::SIZE_V1);
+
+        res.extend_from_slice(&page[page_header.data_offset..]);
+        res
+    }
+
+    #[test]
+    fn current_page_has_magic() {
+        let (page, _) = make_page();
+
+        assert_eq!(&page[..PAGE_MAGIC.len()], &PAGE_MAGIC);
+
+        let unpacker = DataBlockUnPacker::new(page);
+        assert_eq!(unpacker.version(), Ok(FormatVersion::V2));
+        assert_eq!(unpacker.hader().unwrap().this_block_id, 12);
+    }
+
+    #[test]
+    fn decode_v2_page() {
+        let (page, values) = make_page();
+
+        let unpacker = DataBlockUnPacker::new(page);
+        assert!(unpacker.verify());
+        assert_eq!(unpacker.unpack_as::<u32>(), Ok(values));
+    }
+
+    #[test]
+    fn decode_v3_page() {
+        let (page, values) = make_page_with(builder().set_rtc_start(1_614_834_367_089));
+
+        let unpacker = DataBlockUnPacker::new(page);
+        assert_eq!(unpacker.version(), Ok(FormatVersion::V3));
+        assert_eq!(
+            unpacker.hader().unwrap().rtc_start_ms,
+            Some(1_614_834_367_089)
+        );
+        assert_eq!(unpacker.verify_header(), ChecksumStatus::Valid);
+        assert!(unpacker.verify());
+        assert_eq!(unpacker.unpack_as::<u32>(), Ok(values));
+
+        // без времени RTC страница остается в версии 2
+        let (page, _) = make_page();
+        assert_eq!(
+            DataBlockUnPacker::new(page).hader().unwrap().rtc_start_ms,
+            None
+        );
+    }
+
+    #[test]
+    fn decode_legacy_v1_page() {
+        let (page, values) = make_page();
+        let header = DataBlockUnPacker::new(page.clone()).hader();
+
+        let unpacker = DataBlockUnPacker::new(downgrade_to_v1(&page));
+        assert_eq!(unpacker.version(), Ok(FormatVersion::V1));
+        assert_eq!(unpacker.hader(), header);
+        assert_eq!(unpacker.verify_header(), ChecksumStatus::Absent);
+        assert!(unpacker.verify());
+        assert_eq!(unpacker.unpack_as::<u32>(), Ok(values));
+    }
+
+    #[test]
+    fn header_damage_detected_separately() {
+        let (mut page, _) = make_page();
+        // младший байт timestamp
+        page[16] ^= 0x01;
+
+        let unpacker = DataBlockUnPacker::new(page);
+        assert_eq!(
+            unpacker.integrity(),
+            PageIntegrity {
+                header: ChecksumStatus::Invalid,
+                data: ChecksumStatus::Valid,
+            }
+        );
+        assert!(!unpacker.verify());
+    }
+
+    #[test]
+    fn data_damage_detected_separately() {
+        let (mut page, _) = make_page();
+        let data_offset = PageHeader::parse(&page).unwrap().data_offset;
+        page[data_offset + 10] ^= 0x80;
+
+        let unpacker = DataBlockUnPacker::new(page);
+        assert_eq!(
+            unpacker.integrity(),
+            PageIntegrity {
+                header: ChecksumStatus::Valid,
+                data: ChecksumStatus::Invalid,
+            }
+        );
+        assert!(!unpacker.verify());
+    }
+
+    #[test]
+    fn unknown_version_is_not_decoded() {
+        let (mut page, _) = make_page();
+        page[PAGE_MAGIC.len()] = 0xFE;
+
+        let unpacker = DataBlockUnPacker::new(page);
+        assert_eq!(unpacker.version(), Err(Error::UnsupportedVersion(0xFE)));
+        assert!(unpacker.page_header().is_err());
+    }
+}
@@ -0,0 +1,85 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::Recording;
+    use self_recorder_packet::{build_chains, unpack_pages, Chains, FrefOverrides, FrefSource};
+
+    const BLOCK_SIZE: usize = 256;
+
+    /// Сеанс записи с опорной частотой f_ref в заголовках
+    fn record(f_ref: f32) -> Vec<Vec<u8>> {
+        Recording::new(BLOCK_SIZE).fref(f_ref).record(500)
+    }
+
+    /// Первый сеанс с опорной частотой в заголовках, второй - без неё
+    fn sessions(fref_base: f32) -> Chains {
+        let mut storage = record(10_000_000.0);
+        storage.extend(record(0.0));
+        let chains = build_chains(unpack_pages(
+            &storage.concat(),
+            BLOCK_SIZE,
+            fref_base,
+            false,
+        ));
+        assert_eq!(chains.sessions.len(), 2);
+        chains
+    }
+
+    fn first_freq(chains: &Chains, session: usize) -> f32 {
+        chains.sessions[session].pages[0].channels[0].records[0].freq
+    }
+
+    #[test]
+    fn provenance() {
+        let chains = sessions(20_000_000.0);
+
+        for page in chains.sessions[0].pages.iter() {
+            assert_eq!(page.fref.value, 10_000_000.0);
+            assert_eq!(page.fref.source, FrefSource::Header);
+        }
+        for page in chains.sessions[1].pages.iter() {
+            assert_eq!(page.fref.value, 20_000_000.0);
+            assert_eq!(page.fref.source, FrefSource::Default);
+        }
+        assert!(chains.sessions.iter().all(|c| c.fref_changes().is_empty()));
+
+        let ratio = first_freq(&chains, 1) / first_freq(&chains, 0);
+        assert!((ratio - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn session_override() {
+        let mut chains = sessions(20_000_000.0);
+        chains.override_fref(&FrefOverrides::default().set(0, 20_000_000.0));
+
+        for page in chains.sessions[0].pages.iter() {
+            assert_eq!(page.fref.value, 20_000_000.0);
+            assert_eq!(page.fref.source, FrefSource::Override);
+        }
+        let ratio = first_freq(&chains, 1) / first_freq(&chains, 0);
+        assert!((ratio - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn override_without_header_fref() {
+        // второй сеанс без опорной частоты в заголовках распакован без неё
+        let mut chains = sessions(0.0);
+        assert_eq!(first_freq(&chains, 1), 0.0);
+
+        chains.override_fref(&FrefOverrides::default().set(1, 10_000_000.0));
+        let first = &chains.sessions[0].pages;
+        let second = &chains.sessions[1].pages;
+        assert_eq!(first.len(), second.len());
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!(b.fref.value, 10_000_000.0);
+            assert_eq!(b.fref.source, FrefSource::Override);
+            for (a, b) in a.channels.iter().zip(b.channels.iter()) {
+                assert_eq!(a.records.len(), b.records.len());
+                for (a, b) in a.records.iter().zip(b.records.iter()) {
+                    assert_eq!(a.freq, b.freq);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::crc32;
+    use self_recorder_packet::{
+        unpack_pages, ChannelInfo, DataBlockPacker, PushResult, SampleEncoder, SampleEncoding,
+    };
+
+    const BLOCK_SIZE: usize = 4096;
+    const F_REF: f32 = 10_000_000.0;
+
+    #[test]
+    fn five_channels_roundtrip() {
+        let channels = vec![
+            ChannelInfo::new("F1", 10_000, 1),
+            ChannelInfo::new("F2", 20_000, 1),
+            ChannelInfo::new("F3", 30_000, 2),
+            ChannelInfo::new("F4", 40_000, 3),
+            ChannelInfo::new("ACC", 50_000, 4),
+        ];
+        let mut encoder = SampleEncoder::new(
+            DataBlockPacker::builder()
+                .set_channels(channels.clone())
+                .set_write_cfg(250, [1, 1, 2, 3, 4])
+                .set_fref(F_REF)
+                .set_sample_encoding(SampleEncoding::ZigzagVarint)
+                .set_size(BLOCK_SIZE)
+                .build(),
+        );
+
+        // результат счетчика каждого канала на каждом тике, значения не меняются
+        let results = [10_000_000u32, 9_000_000, 8_000_000, 7_000_000, 6_000_000];
+        let mut pushed = [0usize; 5];
+        'fill: for tick in encoder.packer().schedule() {
+            for ch in tick.channels {
+                pushed[ch] += 1;
+                if encoder.push(ch, results[ch]) == PushResult::Full {
+                    break 'fill;
+                }
+            }
+        }
+
+        let page = encoder.into_packer().to_result_full(crc32).unwrap();
+        let pages = unpack_pages(&page, BLOCK_SIZE, 0.0, false);
+        assert_eq!(pages.len(), 1);
+
+        let page = pages[0].as_ref().unwrap();
+        assert!(page.consistant);
+        assert_eq!(page.channels.len(), 5);
+        for (ch, data) in page.channels.iter().enumerate() {
+            assert_eq!(data.info, channels[ch]);
+            assert_eq!(data.records.len(), pushed[ch]);
+
+            let expected = F_REF * channels[ch].target as f32 / results[ch] as f32;
+            for (i, r) in data.records.iter().enumerate() {
+                assert_eq!(r.freq, expected);
+                assert_eq!(
+                    r.timesstamp,
+                    (i as u32 * channels[ch].interleave_ratio * 250) as u64
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_result() {
+        let mut encoder = SampleEncoder::new(
+            DataBlockPacker::builder()
+                .set_targets([10_000, 20_000])
+                .set_write_cfg(100, [1, 0])
+                .set_fref(F_REF)
+                .set_size(BLOCK_SIZE)
+                .build(),
+        );
+
+        // первый отсчет записывается как есть, но переход от 3e9 к 100
+        // не записать разностью i32
+        assert!(encoder.fits(0, 3_000_000_000));
+        assert_eq!(encoder.push(0, 3_000_000_000), PushResult::Success);
+        assert!(!encoder.fits(0, 100));
+        assert_eq!(encoder.push(0, 100), PushResult::OutOfRange);
+        assert_eq!(encoder.push(0, 2_000_000_000), PushResult::Success);
+
+        let mut packer = encoder.into_packer();
+        packer.finish();
+        let page = packer.to_result_full(crc32).unwrap();
+        let pages = unpack_pages(&page, BLOCK_SIZE, F_REF, false);
+        let records = &pages[0].as_ref().unwrap().channels[0].records;
+        assert_eq!(
+            records.iter().map(|r| r.result).collect::<Vec<_>>(),
+            vec![3_000_000_000, 2_000_000_000]
+        );
+    }
+
+    #[test]
+    fn csv_rows_follow_schedule() {
+        let mut encoder = SampleEncoder::new(
+            DataBlockPacker::builder()
+                .set_targets([10_000, 20_000])
+                .set_write_cfg(100, [3, 5])
+                .set_fref(F_REF)
+                .set_sample_encoding(SampleEncoding::ZigzagVarint)
+                .set_size(BLOCK_SIZE)
+                .build(),
+        );
+
+        'fill: for tick in encoder.packer().schedule() {
+            for ch in tick.channels {
+                let result = 1_000_000 + (tick.tick % 7) as u32 * 100 + ch as u32;
+                if encoder.push(ch, result) == PushResult::Full {
+                    break 'fill;
+                }
+            }
+        }
+
+        let page = encoder.into_packer().to_result_full(crc32).unwrap();
+        let pages = unpack_pages(&page, BLOCK_SIZE, 0.0, false);
+        let page = pages[0].as_ref().unwrap();
+
+        let mut timestamps = page
+            .channels
+            .iter()
+            .flat_map(|c| c.records.iter().map(|r| r.timesstamp))
+            .collect::<Vec<_>>();
+        timestamps.sort_unstable();
+        timestamps.dedup();
+
+        let dir = tempdir::TempDir::new("csv_rows_follow_schedule").unwrap();
+        let file = dir.path().join("page.csv");
+        page.save_as_csv(&file).unwrap();
+
+        let csv = std::fs::read_to_string(file).unwrap();
+        // 4 строки описания страницы и заголовок таблицы
+        let rows = csv.lines().skip(5).collect::<Vec<_>>();
+        assert_eq!(rows.len(), timestamps.len());
+
+        let last = rows.last().unwrap().split(';').collect::<Vec<_>>();
+        for (ch, channel) in page.channels.iter().enumerate() {
+            assert_eq!(
+                last[ch + 1],
+                format!("{:.6}", channel.records.last().unwrap().freq)
+            );
+        }
+    }
+}
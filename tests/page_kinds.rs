@@ -0,0 +1,80 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::Recording;
+    use self_recorder_packet::{build_chains, unpack_pages, DataBlockUnPacker, Error, PageKind};
+
+    const BLOCK_SIZE: usize = 512;
+
+    /// Страницы по умолчанию: интервал 1 с, все каналы на каждом тике, без опорной частоты
+    fn record(ticks: u32) -> Vec<Vec<u8>> {
+        Recording::new(BLOCK_SIZE)
+            .base_interval(1000)
+            .ratios([1, 1])
+            .fref(0.0)
+            .record(ticks)
+    }
+
+    /// Запись, стертая и незаписанная страницы, поврежденная и будущей версии
+    fn flash() -> Vec<u8> {
+        let mut pages = record(300);
+        pages.truncate(2);
+
+        let mut corrupt = pages[1].clone();
+        corrupt[100] ^= 0xFF;
+
+        let mut future = pages[1].clone();
+        future[4] = 4;
+
+        pages.extend(vec![
+            vec![0xFF; BLOCK_SIZE],
+            vec![0x00; BLOCK_SIZE],
+            corrupt,
+            future,
+            vec![0xFF; BLOCK_SIZE],
+        ]);
+        pages.concat()
+    }
+
+    const KINDS: [PageKind; 7] = [
+        PageKind::Valid,
+        PageKind::Valid,
+        PageKind::Erased,
+        PageKind::Blank,
+        PageKind::Corrupt,
+        PageKind::UnknownVersion,
+        PageKind::Erased,
+    ];
+
+    #[test]
+    fn classify() {
+        let kinds = flash()
+            .chunks(BLOCK_SIZE)
+            .map(|page| DataBlockUnPacker::new(page).classify())
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, KINDS);
+
+        // стертый хвост обрезанного образа
+        assert_eq!(
+            DataBlockUnPacker::new(&[0xFF; 10][..]).classify(),
+            PageKind::Erased
+        );
+    }
+
+    #[test]
+    fn unpacked_kinds() {
+        for ignore_inconsistant in [false, true] {
+            let pages = unpack_pages(&flash(), BLOCK_SIZE, 0.0, ignore_inconsistant);
+            let kinds = pages.iter().map(PageKind::of).collect::<Vec<_>>();
+            assert_eq!(kinds, KINDS);
+
+            assert_eq!(pages[2].as_ref().err(), Some(&Error::ErasedPage));
+            assert_eq!(pages[3].as_ref().err(), Some(&Error::BlankPage));
+
+            let chains = build_chains(pages);
+            assert_eq!(chains.sessions.len(), 1);
+            assert_eq!(chains.sessions[0].pages.len(), 2);
+        }
+    }
+}
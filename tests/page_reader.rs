@@ -0,0 +1,127 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use std::io::{self, Read};
+
+    use crate::common::Recording;
+    use self_recorder_packet::{
+        unpack_pages, Error, PageData, PageReader, ReadError, SampleEncoding,
+    };
+
+    const BLOCK_SIZE: usize = 512;
+
+    fn record(ticks: u32) -> Vec<u8> {
+        Recording::new(BLOCK_SIZE)
+            .ratios([1, 3])
+            .encoding(SampleEncoding::ZigzagVarint)
+            .record(ticks)
+            .concat()
+    }
+
+    /// Источник, отдающий данные кусками по несколько байт, как канал или порт
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+        /// после скольких байт вернуть ошибку
+        fail_at: Option<usize>,
+        pos: usize,
+    }
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if Some(self.pos) == self.fail_at {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+            let end = (self.pos + self.chunk.min(buf.len()))
+                .min(self.data.len())
+                .min(self.fail_at.unwrap_or(usize::MAX));
+            let n = end - self.pos;
+            buf[..n].copy_from_slice(&self.data[self.pos..end]);
+            self.pos = end;
+            Ok(n)
+        }
+    }
+
+    fn same(a: &PageData, b: &PageData) {
+        assert_eq!(a.header, b.header);
+        assert_eq!(a.integrity, b.integrity);
+        for (a, b) in a.channels.iter().zip(b.channels.iter()) {
+            assert_eq!(a.records.len(), b.records.len());
+            for (a, b) in a.records.iter().zip(b.records.iter()) {
+                assert_eq!(a.timesstamp, b.timesstamp);
+                assert_eq!(a.freq, b.freq);
+            }
+        }
+    }
+
+    #[test]
+    fn same_as_unpack_pages() {
+        let mut data = record(3000);
+        // повреждена страница и обрезана последняя
+        data[BLOCK_SIZE * 2..BLOCK_SIZE * 2 + 16].fill(0xFF);
+        data.truncate(data.len() - BLOCK_SIZE / 2);
+
+        let expected = unpack_pages(&data, BLOCK_SIZE, 0.0, false);
+        assert!(expected.len() > 4);
+
+        for parallel in [1, 3, 16] {
+            let source = Trickle {
+                data: &data,
+                chunk: 7,
+                fail_at: None,
+                pos: 0,
+            };
+            let mut reader = PageReader::new(source, BLOCK_SIZE, 0.0, false).set_parallel(parallel);
+            let pages = reader.by_ref().collect::<Vec<_>>();
+            assert_eq!(reader.truncated_tail(), Some(BLOCK_SIZE / 2));
+
+            assert_eq!(pages.len(), expected.len());
+            for (page, expected) in pages.iter().zip(expected.iter()) {
+                match (page, expected) {
+                    (Ok(page), Ok(expected)) => same(page, expected),
+                    (Err(ReadError::Page(e)), Err(expected)) => assert_eq!(e, expected),
+                    _ => panic!("results differ"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_source() {
+        assert!(PageReader::new(io::empty(), BLOCK_SIZE, 0.0, false)
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Page size must not be zero")]
+    fn zero_page_size() {
+        PageReader::new(io::empty(), 0, 0.0, false);
+    }
+
+    #[test]
+    fn io_error_ends_iteration() {
+        let data = record(3000);
+        let source = Trickle {
+            data: &data,
+            chunk: 100,
+            fail_at: Some(BLOCK_SIZE * 2),
+            pos: 0,
+        };
+
+        let pages = PageReader::new(source, BLOCK_SIZE, 0.0, false)
+            .set_parallel(4)
+            .collect::<Vec<_>>();
+
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0].is_ok() && pages[1].is_ok());
+        assert!(matches!(pages[2], Err(ReadError::Io(_))));
+    }
+
+    #[test]
+    fn page_errors_convert() {
+        let e: ReadError = Error::HeaderCrcMismatch.into();
+        assert!(matches!(e, ReadError::Page(Error::HeaderCrcMismatch)));
+    }
+}
@@ -32,8 +32,9 @@ mod test {
         prevs: &mut [i32; 2],
         fref: u32,
     ) -> bool {
-        if counter % packer.header.interleave_ratio[i] == 0 {
-            let result = result(freqs[i], packer.header.targets[i], fref);
+        if packer.schedule().is_due(counter as u64, i) {
+            let channel = &packer.header.channels[i];
+            let result = result(freqs[i], channel.target, fref);
             let diff = result as i32 - prevs[i];
             prevs[i] = result as i32;
             match packer.push_val(diff) {
@@ -121,6 +122,7 @@ mod test {
             tempdir::TempDir::new("compress_simulation").expect("Failed to create result dir");
 
         unpacked_pages.into_iter().for_each(|page| {
+            let page = page.unwrap();
             assert!(page.consistant);
 
             page.save_as_csv(dir.path().join(format!(
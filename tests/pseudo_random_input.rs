@@ -109,7 +109,7 @@ mod test {
         let res_len = result.len();
         let unpacker = DataBlockUnPacker::new(result);
 
-        assert_eq!(input_data, unpacker.unpack_as());
+        assert_eq!(input_data, unpacker.unpack_as().unwrap());
         println!(
             "Packed {} values to block ({} bytes)",
             input_data.len(),
@@ -137,9 +137,7 @@ mod test {
                 }
                 self_recorder_packet::PushResult::Full => {
                     input_data.push(v);
-                    break block
-                        .to_result_trimmed(|_| 0)
-                        .unwrap();
+                    break block.to_result_trimmed(|_| 0).unwrap();
                 }
                 _ => panic!(),
             }
@@ -148,7 +146,7 @@ mod test {
         let res_len = result.len();
         let unpacker = DataBlockUnPacker::new(result);
 
-        let unpacked = unpacker.unpack_as();
+        let unpacked = unpacker.unpack_as().unwrap();
         assert_eq!(input_data, unpacked);
         println!(
             "Packed {} values to block ({} bytes)",
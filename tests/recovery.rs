@@ -0,0 +1,121 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::{crc32, Recording};
+    use self_recorder_packet::{
+        recover_page, unpack_pages, DataBlockPacker, Error, PageHeader, PageKind, RecoveryLimits,
+        RecoveryStop, SampleEncoding, StopReason,
+    };
+
+    const BLOCK_SIZE: usize = 1024;
+
+    fn record_page(encoding: SampleEncoding) -> Vec<u8> {
+        Recording::new(BLOCK_SIZE).encoding(encoding).first_page()
+    }
+
+    #[test]
+    fn intact_page() {
+        for encoding in [SampleEncoding::Delta, SampleEncoding::ZigzagVarint] {
+            let page = record_page(encoding);
+            let expected = unpack_pages(&page, BLOCK_SIZE, 0.0, false)
+                .remove(0)
+                .unwrap();
+
+            let recovered = recover_page(&page, 0.0, &RecoveryLimits::default()).unwrap();
+            assert!(recovered.is_complete());
+            assert!(recovered.page.consistant);
+            for (a, b) in recovered.page.channels.iter().zip(expected.channels.iter()) {
+                assert_eq!(a.records.len(), b.records.len());
+            }
+        }
+    }
+
+    #[test]
+    fn salvage_prefix() {
+        for encoding in [SampleEncoding::Delta, SampleEncoding::ZigzagVarint] {
+            let page = record_page(encoding);
+            let expected = unpack_pages(&page, BLOCK_SIZE, 0.0, false)
+                .remove(0)
+                .unwrap();
+
+            let page_header = PageHeader::parse(&page).unwrap();
+            let damage = page_header.data_offset + page_header.header.data_len as usize * 3 / 4;
+            let mut damaged = page.clone();
+            damaged[damage] ^= 0xFF;
+            damaged[damage + 1] ^= 0xFF;
+
+            let pages = unpack_pages(&damaged, BLOCK_SIZE, 0.0, false);
+            assert_eq!(PageKind::of(&pages[0]), PageKind::Corrupt);
+
+            // результаты в записи меняются не больше чем на 70 из 9 000 000
+            let limits = RecoveryLimits {
+                max_step: 0.001,
+                ..Default::default()
+            };
+            let recovered = recover_page(&damaged, 0.0, &limits).unwrap();
+            assert!(!recovered.page.consistant);
+            let stop = recovered.stop.expect("damage not detected");
+
+            for (a, b) in recovered.page.channels.iter().zip(expected.channels.iter()) {
+                // отсчеты из неповрежденной части страницы
+                assert!(a.records.len() >= b.records.len() / 2);
+                assert!(a.records.len() < b.records.len());
+                for (a, b) in a
+                    .records
+                    .iter()
+                    .zip(b.records.iter())
+                    .take(b.records.len() / 2)
+                {
+                    assert_eq!(a.timesstamp, b.timesstamp);
+                    assert_eq!(a.freq, b.freq);
+                }
+            }
+            assert!(stop.tick > 0);
+        }
+    }
+
+    #[test]
+    fn delta_min() {
+        // два тика, затем разность i32::MIN в канале FP
+        let mut packer = DataBlockPacker::builder()
+            .set_targets([10_000_000, 10_000_000])
+            .set_write_cfg(100, [1, 1])
+            .set_size(BLOCK_SIZE)
+            .build();
+        for v in [10_000_000i32, 9_000_000, 5, 5, i32::MIN, 5] {
+            packer.push_val(v);
+        }
+        packer.finish();
+        let page = packer.to_result_full(crc32).unwrap();
+
+        let recovered = recover_page(&page, 0.0, &RecoveryLimits::default()).unwrap();
+        assert_eq!(
+            recovered.stop,
+            Some(RecoveryStop {
+                offset: 16,
+                tick: 2,
+                reason: StopReason::Malformed(Error::DeltaOverflow {
+                    channel: 0,
+                    sample: 2
+                }),
+            })
+        );
+        assert!(recovered.page.channels.iter().all(|c| c.records.len() == 2));
+    }
+
+    #[test]
+    fn unreadable_pages() {
+        assert_eq!(
+            recover_page(&[0xFF; BLOCK_SIZE], 0.0, &RecoveryLimits::default()).err(),
+            Some(Error::ErasedPage)
+        );
+
+        let mut page = record_page(SampleEncoding::ZigzagVarint);
+        page[4] = 4;
+        assert_eq!(
+            recover_page(&page, 0.0, &RecoveryLimits::default()).err(),
+            Some(Error::UnsupportedVersion(4))
+        );
+    }
+}
@@ -0,0 +1,82 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::Recording;
+    use self_recorder_packet::{
+        build_chains, unpack_pages, SampleEncoding, SeriesGap, SessionSeries,
+    };
+
+    const BLOCK_SIZE: usize = 256;
+    const BASE_INTERVAL_MS: u32 = 100;
+
+    fn record(ticks: u32) -> Vec<Vec<u8>> {
+        Recording::new(BLOCK_SIZE)
+            .base_interval(BASE_INTERVAL_MS)
+            .encoding(SampleEncoding::ZigzagVarint)
+            .record(ticks)
+    }
+
+    fn series(storage: &[Vec<u8>]) -> SessionSeries {
+        let chains = build_chains(unpack_pages(&storage.concat(), BLOCK_SIZE, 0.0, false));
+        assert_eq!(chains.sessions.len(), 1);
+        chains.sessions[0].series()
+    }
+
+    #[test]
+    fn continuous_session() {
+        const TICKS: u32 = 1000;
+        let series = series(&record(TICKS));
+
+        assert!(series.gaps.is_empty());
+        assert_eq!(series.channels.len(), 2);
+
+        // FP опрашивается на каждом тике, каждый тик записан один раз
+        let fp = &series.channels[0].records;
+        assert_eq!(fp.len(), TICKS as usize);
+        for (tick, r) in fp.iter().enumerate() {
+            assert_eq!(r.timesstamp, tick as u64 * BASE_INTERVAL_MS as u64);
+        }
+
+        let ft = &series.channels[1].records;
+        assert!(ft.windows(2).all(|w| w[0].timesstamp < w[1].timesstamp));
+    }
+
+    #[test]
+    fn damaged_page_is_a_gap() {
+        let mut storage = record(1000);
+        // данные страницы повреждены, она остается в цепочке, но без отсчетов
+        storage[2][100] ^= 0xFF;
+
+        let series = series(&storage);
+        assert_eq!(series.gaps.len(), 1);
+
+        let SeriesGap { from_ms, to_ms } = series.gaps[0];
+        let fp = &series.channels[0].records;
+        let before = fp.iter().rev().find(|r| r.timesstamp < from_ms).unwrap();
+        let after = fp.iter().find(|r| r.timesstamp >= from_ms).unwrap();
+        assert_eq!(before.timesstamp + BASE_INTERVAL_MS as u64, from_ms);
+        assert_eq!(after.timesstamp, to_ms);
+    }
+
+    #[test]
+    fn single_csv() {
+        let mut storage = record(1000);
+        storage[2][100] ^= 0xFF;
+        let series = series(&storage);
+
+        let dir = tempdir::TempDir::new("session_series").unwrap();
+        let file = dir.path().join("session.csv");
+        series.save_as_csv(&file).unwrap();
+
+        let csv = std::fs::read_to_string(file).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[3],
+            "Время;Частота давления, Гц;Частота температуры, Гц"
+        );
+        assert_eq!(lines.iter().filter(|l| l.starts_with("Разрыв;")).count(), 1);
+        // строка на каждый тик с отсчетами
+        assert_eq!(lines.len() - 4 - 1, series.channels[0].records.len());
+    }
+}
@@ -0,0 +1,53 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::{record_chain_with, Recording};
+    use self_recorder_packet::{
+        build_chains, unpack_pages, AlertKind, Telemetry, TelemetryThresholds,
+    };
+
+    const BLOCK_SIZE: usize = 256;
+    const BASE_INTERVAL_MS: u32 = 100;
+    const HOUR: u64 = 60 * 60 * 1000;
+
+    /// Батарея разряжается на 0.36 В/ч, процессор перегревается на 1000-м тике
+    fn record(ticks: u32) -> Vec<u8> {
+        let builder = Recording::new(BLOCK_SIZE)
+            .base_interval(BASE_INTERVAL_MS)
+            .builder();
+        record_chain_with(builder, ticks, |tick| {
+            let t_cpu = if tick >= 1000 { 90.0 } else { 25.0 };
+            (t_cpu, 3.6 - tick as f32 * 1e-5)
+        })
+        .concat()
+    }
+
+    #[test]
+    fn session_telemetry() {
+        let storage = record(3000);
+        let chains = build_chains(unpack_pages(&storage, BLOCK_SIZE, 0.0, false));
+        assert_eq!(chains.sessions.len(), 1);
+        let session = &chains.sessions[0];
+
+        let telemetry = session.telemetry();
+        assert_eq!(telemetry, Telemetry::from_pages(&session.pages));
+        assert_eq!(telemetry.points.len(), session.pages.len());
+        assert!(telemetry
+            .points
+            .windows(2)
+            .all(|w| w[0].timestamp < w[1].timestamp && w[0].v_bat > w[1].v_bat));
+
+        let forecast = telemetry.battery_forecast(3.0, None).unwrap();
+        assert!((forecast.volts_per_hour + 0.36).abs() < 0.01);
+        // от 3.6 В до 3.0 В - 1 ч 40 мин от начала записи
+        let depleted_at = forecast.depleted_at_ms.unwrap() as f64 / HOUR as f64;
+        assert!((depleted_at - 100.0 / 60.0).abs() < 0.01, "{}", depleted_at);
+
+        let alerts = telemetry.alerts(&TelemetryThresholds::default());
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, AlertKind::Overheat);
+        assert!(alerts[0].timestamp <= 1000 * BASE_INTERVAL_MS as u64);
+        assert_eq!(alerts[0].value, 90.0);
+    }
+}
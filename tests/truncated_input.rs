@@ -0,0 +1,136 @@
+mod common;
+
+#[cfg(feature = "unpacker")]
+mod test {
+    use crate::common::crc32;
+    use self_recorder_packet::{
+        truncated_tail, unpack_pages, DataBlockPacker, DataBlockUnPacker, Error, PageHeader,
+        PushResult,
+    };
+
+    const BLOCK_SIZE: usize = 4096;
+
+    fn make_packer() -> DataBlockPacker {
+        let mut packer = DataBlockPacker::builder()
+            .set_targets([12_000_000, 11_000_000])
+            .set_size(BLOCK_SIZE)
+            .build();
+
+        // разности отсчетов: первые значения каналов, затем небольшие колебания
+        for i in 0i32.. {
+            let diff = if i < 2 { 12_000_000 } else { i % 3 - 1 };
+            if packer.push_val(diff) == PushResult::Full {
+                break;
+            }
+        }
+        packer
+    }
+
+    /// Конец сжатых данных в странице
+    fn data_end(page: &[u8]) -> usize {
+        let page_header = PageHeader::parse(page).unwrap();
+        page_header.data_offset + page_header.header.data_len as usize
+    }
+
+    #[test]
+    fn empty_buffer() {
+        assert_eq!(
+            DataBlockUnPacker::try_new(Vec::new()).err(),
+            Some(Error::ShortPage { need: 4, got: 0 })
+        );
+
+        let unpacker = DataBlockUnPacker::new(Vec::new());
+        assert!(unpacker.hader().is_err());
+        assert!(unpacker.unpack_data().is_err());
+        assert!(!unpacker.verify());
+
+        assert!(unpack_pages(&[], BLOCK_SIZE, 0.0, false).is_empty());
+    }
+
+    #[test]
+    fn every_prefix_is_rejected_safely() {
+        let page = make_packer().to_result_full(crc32).unwrap();
+        let data_end = data_end(&page);
+
+        for len in 0..data_end {
+            let prefix = page[..len].to_vec();
+
+            match DataBlockUnPacker::try_new(prefix.clone()) {
+                Err(Error::ShortPage { need, got }) => {
+                    assert_eq!(got, len);
+                    assert!(need > len);
+                }
+                Err(e) => panic!("prefix {}: unexpected error {}", len, e),
+                Ok(_) => panic!("prefix {} accepted", len),
+            }
+
+            let unpacker = DataBlockUnPacker::new(prefix);
+            assert!(unpacker.unpack_as::<u32>().is_err());
+            assert!(unpacker.check().is_err());
+            assert!(!unpacker.verify());
+        }
+
+        for len in data_end..=page.len() {
+            let unpacker = DataBlockUnPacker::try_new(page[..len].to_vec()).unwrap();
+            assert_eq!(unpacker.check(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn truncated_legacy_header() {
+        // страница v1 без сигнатуры короче своего 56-байтного заголовка
+        let page = vec![0x01u8; 40];
+
+        assert_eq!(
+            DataBlockUnPacker::try_new(page).err(),
+            Some(Error::ShortPage { need: 44, got: 40 })
+        );
+    }
+
+    #[test]
+    fn truncated_last_page() {
+        let page = make_packer().to_result_full(crc32).unwrap();
+        let data_end = data_end(&page);
+
+        let mut dump = page.clone();
+        dump.extend_from_slice(&page[..data_end - 1]);
+
+        assert_eq!(truncated_tail(page.len(), BLOCK_SIZE), None);
+        assert_eq!(truncated_tail(dump.len(), BLOCK_SIZE), Some(data_end - 1));
+
+        let pages = unpack_pages(&dump, BLOCK_SIZE, 0.0, false);
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].as_ref().unwrap().consistant);
+        assert_eq!(
+            pages[1].as_ref().err(),
+            Some(&Error::ShortPage {
+                need: data_end,
+                got: data_end - 1
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Page size must not be zero")]
+    fn zero_page_size() {
+        unpack_pages(&[0xFF; 16], 0, 0.0, false);
+    }
+
+    #[test]
+    fn last_page_with_complete_data() {
+        let page = make_packer().to_result_full(crc32).unwrap();
+        let trimmed = make_packer().to_result_trimmed(crc32).unwrap();
+        assert!(trimmed.len() < BLOCK_SIZE);
+
+        let mut dump = page;
+        dump.extend_from_slice(&trimmed);
+
+        let pages = unpack_pages(&dump, BLOCK_SIZE, 0.0, false);
+        assert_eq!(pages.len(), 2);
+        for page in pages {
+            let page = page.unwrap();
+            assert!(page.consistant);
+            assert!(!page.channels[0].records.is_empty());
+        }
+    }
+}